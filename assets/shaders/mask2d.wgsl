@@ -1,13 +1,72 @@
 #import bevy_sprite::mesh2d_vertex_output::VertexOutput
 
+struct MaskSettings {
+    uv_matrix: mat2x2<f32>,
+    uv_translation: vec2<f32>,
+    channel: u32,
+    invert: u32,
+    threshold: u32,
+    cutoff: f32,
+    softness: f32,
+    combine: u32,
+    premultiplied_main: u32,
+};
+
 @group(2) @binding(0) var main_texture : texture_2d<f32>;
 @group(2) @binding(1) var main_sampler : sampler;
 @group(2) @binding(2) var mask_texture : texture_2d<f32>;
+@group(2) @binding(3) var<uniform> settings : MaskSettings;
+@group(2) @binding(4) var mask_sampler : sampler;
+
+// Selected channel of the mask colour
+fn mask_channel(m: vec4<f32>) -> f32 {
+    switch settings.channel {
+        case 0u: { return m.r; }
+        case 1u: { return m.g; }
+        case 2u: { return m.b; }
+        case 4u: { return dot(m.rgb, vec3<f32>(0.2126, 0.7152, 0.0722)); }
+        default: { return m.a; }
+    }
+}
+
+// Channel value, then inversion, then the threshold cutoff
+fn mask_value(m: vec4<f32>) -> f32 {
+    var v = mask_channel(m);
+    if settings.invert != 0u {
+        v = 1.0 - v;
+    }
+    if settings.threshold != 0u {
+        let half_width = settings.softness * 0.5;
+        if half_width <= 0.0 {
+            v = step(settings.cutoff, v);
+        } else {
+            v = smoothstep(settings.cutoff - half_width, settings.cutoff + half_width, v);
+        }
+    }
+    return v;
+}
+
+// Output alpha from the main texture's alpha and the mask value
+fn combine_alpha(main_alpha: f32, mask: f32) -> f32 {
+    switch settings.combine {
+        case 0u: { return mask; }
+        case 2u: { return min(main_alpha, mask); }
+        case 3u: { return max(main_alpha - mask, 0.0); }
+        default: { return main_alpha * mask; }
+    }
+}
 
 @fragment
 fn fragment(mesh: VertexOutput
 ) -> @location(0) vec4<f32> {
     let c = textureSample(main_texture, main_sampler, mesh.uv);
-    let m = textureSample(mask_texture, main_sampler, mesh.uv);
-    return vec4<f32>(c.rgb, m.a);
+    let mask_uv = settings.uv_matrix * mesh.uv + settings.uv_translation;
+    let m = textureSample(mask_texture, mask_sampler, mask_uv);
+    // Premultiplied, see `Mask2DMaterial::specialize`
+    let a = combine_alpha(c.a, mask_value(m));
+    var rgb = c.rgb;
+    if settings.premultiplied_main != 0u {
+        rgb = select(vec3<f32>(0.0), c.rgb / c.a, c.a > 0.0);
+    }
+    return vec4<f32>(rgb * a, a);
 }
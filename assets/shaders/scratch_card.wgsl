@@ -1,4 +1,5 @@
 #import bevy_sprite::mesh2d_vertex_output::VertexOutput
+#import bevy_sprite::mesh2d_view_bindings::{globals, view}
 
 @group(2) @binding(0) var reveal_texture : texture_2d<f32>;
 @group(2) @binding(1) var reveal_sampler : sampler;
@@ -7,8 +8,151 @@
 @group(2) @binding(4) var cover_texture : texture_2d<f32>;
 @group(2) @binding(5) var cover_sampler : sampler;
 
-const REPEAT_FACTOR : f32 = 3;
+struct RevealAnimation {
+    origin: vec2<f32>,
+    progress: f32,
+    softness: f32,
+    noise_scale: f32,
+    style: u32,
+}
 
+@group(2) @binding(6) var<uniform> reveal_animation : RevealAnimation;
+
+struct CoverSettings {
+    tint: vec4<f32>,
+    tiling: vec2<f32>,
+    offset: vec2<f32>,
+    shimmer: f32,
+    foil_color: vec4<f32>,
+    foil_speed: f32,
+    foil_map_scale: f32,
+    foil_sharpness: f32,
+    has_foil_map: u32,
+    transparent_reveal: u32,
+}
+
+@group(2) @binding(7) var<uniform> cover : CoverSettings;
+@group(2) @binding(8) var foil_map : texture_2d<f32>;
+@group(2) @binding(9) var foil_sampler : sampler;
+
+struct EdgeSettings {
+    highlight_color: vec4<f32>,
+    light_direction: vec2<f32>,
+    shadow_offset: vec2<f32>,
+    width: f32,
+    rim_darkness: f32,
+    highlight: f32,
+    shadow: f32,
+}
+
+@group(2) @binding(10) var<uniform> edge : EdgeSettings;
+
+const STYLE_FADE : u32 = 0u;
+const STYLE_RADIAL_WIPE : u32 = 1u;
+const STYLE_NOISE_DISSOLVE : u32 = 2u;
+
+fn hash(p: vec2<f32>) -> f32 {
+    return fract(sin(dot(p, vec2<f32>(127.1, 311.7))) * 43758.5453);
+}
+
+// Smooth value noise in 0..1
+fn value_noise(p: vec2<f32>) -> f32 {
+    let cell = floor(p);
+    let f = fract(p);
+    let u = f * f * (3.0 - 2.0 * f);
+    let a = hash(cell);
+    let b = hash(cell + vec2<f32>(1.0, 0.0));
+    let c = hash(cell + vec2<f32>(0.0, 1.0));
+    let d = hash(cell + vec2<f32>(1.0, 1.0));
+    return mix(mix(a, b, u.x), mix(c, d, u.x), u.y);
+}
+
+// Foil glints: a light circles over the card, the map tilts the surface normal
+// and diagonal bands drift with it, all seen from the camera's position
+fn foil_highlight(mesh: VertexOutput) -> vec3<f32> {
+    if (cover.shimmer <= 0.0) {
+        return vec3<f32>(0.0);
+    }
+    let t = globals.time * cover.foil_speed;
+
+    var normal = normalize(mesh.world_normal);
+    if (cover.has_foil_map != 0u) {
+        let map_uv = mesh.uv * cover.foil_map_scale + vec2<f32>(t * 0.05, 0.0);
+        let tilt = textureSample(foil_map, foil_sampler, map_uv).xy * 2.0 - 1.0;
+        normal = normalize(normal + vec3<f32>(tilt, 0.0));
+    }
+
+    let view_dir = normalize(view.world_position - mesh.world_position.xyz);
+    let light_dir = normalize(vec3<f32>(cos(t), sin(t), 1.5));
+    let half_dir = normalize(view_dir + light_dir);
+    let glint = pow(max(dot(normal, half_dir), 0.0), cover.foil_sharpness);
+
+    let phase = (mesh.uv.x + mesh.uv.y) * 6.0 + t + dot(normal.xy, vec2<f32>(4.0));
+    let bands = pow(0.5 + 0.5 * sin(phase), 8.0);
+    return cover.foil_color.rgb * cover.shimmer * (glint + bands);
+}
+
+// How much of the cover the reveal animation has removed at this uv, 0..1
+fn animated_reveal(uv: vec2<f32>) -> f32 {
+    let progress = reveal_animation.progress;
+    if (progress <= 0.0) {
+        return 0.0;
+    }
+    if (reveal_animation.style == STYLE_FADE) {
+        return progress;
+    }
+
+    // Pixels with a key below the moving threshold are uncovered, the edge
+    // is `softness` wide and the threshold runs from 0 to past the largest key
+    var key = value_noise(uv * reveal_animation.noise_scale);
+    if (reveal_animation.style == STYLE_RADIAL_WIPE) {
+        let corner = max(reveal_animation.origin, 1.0 - reveal_animation.origin);
+        key = distance(uv, reveal_animation.origin) / max(length(corner), 1e-4);
+    }
+    let softness = max(reveal_animation.softness, 1e-4);
+    let threshold = progress * (1.0 + softness);
+    return 1.0 - smoothstep(threshold - softness, threshold, key);
+}
+
+
+// Mask value including the reveal animation
+fn mask_at(uv: vec2<f32>) -> f32 {
+    let scratched = textureSample(scratch_mask, scratch_mask_sampler, uv).r;
+    return max(scratched, animated_reveal(uv));
+}
+
+// Cover and reveal colours shaded by the slope of the mask around the hole edges
+struct EdgeShading {
+    cover_rgb: vec3<f32>,
+    reveal_rgb: vec3<f32>,
+}
+
+fn shade_edges(uv: vec2<f32>, mask_value: f32, cover_rgb: vec3<f32>, reveal_rgb: vec3<f32>) -> EdgeShading {
+    var out = EdgeShading(cover_rgb, reveal_rgb);
+    if (edge.rim_darkness <= 0.0 && edge.highlight <= 0.0 && edge.shadow <= 0.0) {
+        return out;
+    }
+    let texel = 1.0 / vec2<f32>(textureDimensions(scratch_mask));
+    let step = texel * edge.width;
+
+    // Central differences point from cover into the hole
+    let gradient = vec2<f32>(
+        mask_at(uv + vec2<f32>(step.x, 0.0)) - mask_at(uv - vec2<f32>(step.x, 0.0)),
+        mask_at(uv + vec2<f32>(0.0, step.y)) - mask_at(uv - vec2<f32>(0.0, step.y)),
+    ) * 0.5;
+    let slope = saturate(length(gradient) * 2.0);
+    let on_cover = 1.0 - mask_value;
+
+    // Cover darkens along the rim, the side of the hole facing the light catches it
+    out.cover_rgb *= 1.0 - edge.rim_darkness * slope * on_cover;
+    let facing = saturate(dot(normalize(gradient + vec2<f32>(1e-6)), edge.light_direction));
+    out.cover_rgb += edge.highlight_color.rgb * edge.highlight * slope * facing * on_cover;
+
+    // Cover towards the light from this point casts a shadow into the revealed area
+    let caster = 1.0 - mask_at(uv - edge.shadow_offset * texel);
+    out.reveal_rgb *= 1.0 - edge.shadow * caster * mask_value;
+    return out;
+}
 
 @fragment
 fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
@@ -16,22 +160,28 @@ fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
     let reveal_color  = textureSample(reveal_texture, reveal_sampler, mesh.uv);
 
     // ---- 2. 取遮罩灰階（假設 r 通道即 alpha）
-    let mask_value    = textureSample(scratch_mask, scratch_mask_sampler, mesh.uv).r;
+    let mask_value    = mask_at(mesh.uv);
 
     // ---- 3. 以 fract() 做 repeat，取覆蓋層星星圖
-    let pattern_uv    = fract(mesh.uv * REPEAT_FACTOR);
-    let cover_color = textureSample(cover_texture, cover_sampler, pattern_uv);
+    let pattern_uv    = fract(mesh.uv * cover.tiling + cover.offset);
+    let cover_sample  = textureSample(cover_texture, cover_sampler, pattern_uv) * cover.tint;
+    let foil_rgb      = cover_sample.rgb + foil_highlight(mesh);
+    let shaded        = shade_edges(mesh.uv, mask_value, foil_rgb, reveal_color.rgb);
+    let cover_color   = vec4<f32>(shaded.cover_rgb, cover_sample.a);
 
     // ---- 4. 依遮罩灰階混合：
     //      mask_value = 0   -> 完全顯示 cover_color (星星圖案)
     //      mask_value = 1   -> 完全顯示 reveal_color (獎品)
-    let out_rgb = mix(cover_color.rgb, reveal_color.rgb, mask_value);
-    // 如果想要跟著 mask_value 改變透明度，就把下行改成 mask_value
+    //      transparent_reveal 時刮開處變透明，不使用獎品圖
+    if (cover.transparent_reveal != 0u) {
+        return vec4<f32>(cover_color.rgb, cover_color.a * (1.0 - mask_value));
+    }
+    let out_rgb = mix(cover_color.rgb, shaded.reveal_rgb, mask_value);
     let out_a   = 1.0;
-    
+
     // 調試：直接顯示遮罩值作為灰階
     // return vec4<f32>(mask_value, mask_value, mask_value, 1.0);
-    
+
     // 正常渲染
     return vec4<f32>(out_rgb, out_a);
 }
\ No newline at end of file
@@ -2,9 +2,12 @@
 @group(0) @binding(0) var mask_texture: texture_storage_2d<r8unorm, read_write>;
 
 struct PaintData {
+    // Brush centre in mask UV, snapped back to the pixel grid below
     position: vec2<f32>,
     radius: f32,
     paint_value: f32,
+    // First pixel of the stamp's bounds, the dispatch only covers those
+    origin: vec2<u32>,
 }
 
 @group(0) @binding(1) var<uniform> paint_data: PaintData;
@@ -12,7 +15,7 @@ struct PaintData {
 @compute @workgroup_size(8, 8, 1)
 fn update_mask(@builtin(global_invocation_id) global_id: vec3<u32>) {
     let tex_size = textureDimensions(mask_texture);
-    let coords = global_id.xy;
+    let coords = global_id.xy + paint_data.origin;
     
     // Check if within texture bounds
     if (coords.x >= tex_size.x || coords.y >= tex_size.y) {
@@ -21,11 +24,12 @@ fn update_mask(@builtin(global_invocation_id) global_id: vec3<u32>) {
     
     // Calculate distance from current pixel to brush center
     let pixel_pos = vec2<f32>(coords);
-    let paint_pos = paint_data.position * vec2<f32>(tex_size);
-    let distance = length(pixel_pos - paint_pos);
+    let paint_pos = round(paint_data.position * vec2<f32>(tex_size));
+    let offset = pixel_pos - paint_pos;
     
     // If within brush radius, update mask value
-    if (distance <= paint_data.radius) {
+    // (squared integer distance keeps the result identical to the CPU painter)
+    if (dot(offset, offset) <= paint_data.radius * paint_data.radius) {
         let current_value = textureLoad(mask_texture, coords).r;
         let new_value = max(current_value, paint_data.paint_value);
         textureStore(mask_texture, coords, vec4<f32>(new_value, 0.0, 0.0, 1.0));
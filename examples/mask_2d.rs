@@ -1,29 +1,8 @@
 use bevy::prelude::*;
-use bevy::render::render_resource::{AsBindGroup, ShaderRef};
-use bevy::sprite::{AlphaMode2d, Material2d, Material2dPlugin};
+use mask_2d::mask2d::{Mask2DMaterial, Mask2DPlugin, MaskSampler, MaskThreshold, MaskTransform};
 
-#[derive(Asset, AsBindGroup, TypePath, Debug, Clone)]
-pub struct Mask2DMaterial {
-    #[texture(0)]
-    #[sampler(1)]
-    pub main_texture: Handle<Image>,
-
-    #[texture(2)]
-    pub mask_texture: Handle<Image>,
-}
-
-impl Material2d for Mask2DMaterial {
-    fn fragment_shader() -> ShaderRef {
-        "shaders/mask2d.wgsl".into()
-    }
-
-    fn vertex_shader() -> ShaderRef {
-        ShaderRef::Default
-    }
-    fn alpha_mode(&self) -> AlphaMode2d {
-        AlphaMode2d::Blend
-    }
-}
+const MASK_ORBIT_RADIUS: f32 = 0.2;
+const MASK_SPIN_SPEED: f32 = 0.5;
 
 fn main() {
     App::new()
@@ -32,9 +11,10 @@ fn main() {
                 watch_for_changes_override: Some(true),
                 ..default()
             }),
-            Material2dPlugin::<Mask2DMaterial>::default(),
+            Mask2DPlugin,
         ))
         .add_systems(Startup, setup)
+        .add_systems(Update, animate_mask)
         .run();
 }
 
@@ -49,9 +29,19 @@ fn setup(
     let main_tex = asset_server.load("images/main.png");
     let mask_tex = asset_server.load("images/mask.png");
 
+    // Cut the mask's alpha out with a slightly soft edge
     let material = materials.add(Mask2DMaterial {
-        main_texture: main_tex,
-        mask_texture: mask_tex,
+        threshold: Some(MaskThreshold {
+            cutoff: 0.5,
+            softness: 0.1,
+        }),
+        // Clamped so the moving mask doesn't wrap around the quad
+        mask_sampler: MaskSampler::default(),
+        mask_transform: MaskTransform {
+            scale: Vec2::splat(0.8),
+            ..default()
+        },
+        ..Mask2DMaterial::new(main_tex, mask_tex)
     });
 
     let mesh_handle = meshes.add(Rectangle::from_size(Vec2::splat(200.0)));
@@ -61,3 +51,18 @@ fn setup(
         Transform::from_xyz(0.0, 0.0, 0.0),
     ));
 }
+
+/// Sweep the mask around the quad like a spotlight, slowly turning it
+fn animate_mask(
+    time: Res<Time>,
+    mut materials: ResMut<Assets<Mask2DMaterial>>,
+    quads: Query<&MeshMaterial2d<Mask2DMaterial>>,
+) {
+    let t = time.elapsed_secs();
+    for material in &quads {
+        if let Some(material) = materials.get_mut(&material.0) {
+            material.mask_transform.offset = Vec2::from_angle(t) * MASK_ORBIT_RADIUS;
+            material.mask_transform.rotation = t * MASK_SPIN_SPEED;
+        }
+    }
+}
@@ -1,199 +1,209 @@
-use bevy::{
-    asset::RenderAssetUsages,
-    image::ImageSampler,
-    prelude::*,
-    render::render_resource::{
-        AsBindGroup, Extent3d, ShaderRef, TextureDimension, TextureFormat, TextureUsages,
-    },
-    sprite::{AlphaMode2d, Material2d, Material2dPlugin},
-    window::PrimaryWindow,
+use bevy::prelude::*;
+use mask_2d::scratch_card::{
+    PrizeMatched, PrizeOutcome, PrizeSymbol, ScratchBackend, ScratchBlend, ScratchBrush,
+    ScratchCard, ScratchCardLayout, ScratchCardPlugin, ScratchCover, ScratchDebris, ScratchEdge,
+    ScratchHistory, ScratchMask, ScratchRegion, ScratchRegionRevealed, ScratchRegions,
+    ScratchRevealAnimation, ScratchRevealStyle, ScratchThresholdReached,
 };
 
-const BRUSH_RADIUS: i32 = 20;
-
-#[derive(Asset, AsBindGroup, TypePath, Debug, Clone)]
-pub struct ScratchCardMaterial {
-    #[texture(0)]
-    #[sampler(1)]
-    pub reveal_texture: Handle<Image>,
-    #[texture(2)]
-    #[sampler(3)]
-    pub scratch_mask: Handle<Image>,
-    #[texture(4)]
-    #[sampler(5)]
-    pub cover_layer: Handle<Image>,
-}
-
-/// Scratch card resources structure storing texture handles and mask data
-#[derive(Resource)]
-pub struct ScratchResources {
-    pub reveal_texture: Handle<Image>,
-    pub cover_texture: Handle<Image>,
-    pub mask_data: Vec<u8>,
-    pub size: u32,
-}
-
-impl Material2d for ScratchCardMaterial {
-    fn fragment_shader() -> ShaderRef {
-        "shaders/scratch_card.wgsl".into()
-    }
-
-    fn vertex_shader() -> ShaderRef {
-        ShaderRef::Default
-    }
-
-    fn alpha_mode(&self) -> AlphaMode2d {
-        AlphaMode2d::Blend
-    }
-}
+const GRID_COLUMNS: u32 = 3;
+const GRID_ROWS: u32 = 2;
+const CARD_SIZE: Vec2 = Vec2::new(256.0, 192.0);
+const CARD_SPACING: f32 = 24.0;
+const AUTO_REVEAL_THRESHOLD: f32 = 0.7;
+const CARD_TILT: f32 = 0.06;
+const PRIZE_SLOTS: u32 = 3;
+const REVEAL_DURATION: f32 = 0.8;
 
 pub fn main() {
     App::new()
         .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
-        .add_plugins(Material2dPlugin::<ScratchCardMaterial>::default())
+        .add_plugins(ScratchCardPlugin)
         .add_systems(Startup, setup)
-        .add_systems(Update, handle_mouse_input)
+        .add_systems(
+            Update,
+            (auto_reveal, undo_redo, announce_matches, announce_regions),
+        )
         .run();
 }
 
-fn setup(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ScratchCardMaterial>>,
-    mut images: ResMut<Assets<Image>>,
-    asset_server: Res<AssetServer>,
-) {
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
     // Load static assets
     let reveal = asset_server.load("images/prize.png");
     let star = asset_server.load("images/star_pattern.png");
-
-    // Create initial black mask (0 = show cover layer star pattern)
-    let initial_mask_data = vec![0u8; 512 * 512];
-    let initial_mask = images.add(create_mask_image(&initial_mask_data, 512));
-
-    // Store resource information
-    commands.insert_resource(ScratchResources {
-        reveal_texture: reveal.clone(),
-        cover_texture: star.clone(),
-        mask_data: initial_mask_data,
-        size: 512,
-    });
-
-    // Create initial material with correct mask
-    let material = materials.add(ScratchCardMaterial {
-        reveal_texture: reveal,
-        scratch_mask: initial_mask,
-        cover_layer: star,
-    });
+    let coin = asset_server.load("images/ferris.png");
+    let symbols = vec![
+        PrizeSymbol::new("ferris", coin.clone()),
+        PrizeSymbol::new("bevy", asset_server.load("images/icon.png")),
+    ];
+    // A row of square slots across the middle of the card
+    let slots = (0..PRIZE_SLOTS)
+        .map(|slot| {
+            let width = 1.0 / PRIZE_SLOTS as f32;
+            let min = Vec2::new(slot as f32 * width, 0.5 - width * 0.5);
+            Rect::from_corners(min, min + width).inflate(-0.02)
+        })
+        .collect::<Vec<_>>();
+
+    // Bottom row brushes: hard circle, soft airbrush building up over passes, image stamp
+    let brushes = [
+        ScratchBrush::default(),
+        ScratchBrush {
+            radius: 28.0,
+            hardness: 0.2,
+            opacity: 0.35,
+            blend: ScratchBlend::Additive,
+            ..default()
+        },
+        ScratchBrush {
+            radius: 24.0,
+            stamp_image: Some(coin),
+            ..default()
+        },
+    ];
 
     // Scene setup
     commands.spawn(Camera2d);
-    commands.spawn((
-        Mesh2d(meshes.add(Rectangle::from_size(Vec2::splat(512.0)))),
-        MeshMaterial2d(material),
-        Transform::from_xyz(0.0, 0.0, 0.0),
-        ScratchQuad, // Mark this quad for easy material updates
-    ));
-}
 
-/// Component to mark the scratch card quad
-#[derive(Component)]
-struct ScratchQuad;
+    // Lay out a grid of independent cards centred on the origin,
+    // the top row is painted by the compute shader when the GPU supports it
+    let step = CARD_SIZE + CARD_SPACING;
+    let origin = -Vec2::new(GRID_COLUMNS as f32 - 1.0, GRID_ROWS as f32 - 1.0) * step * 0.5;
+    for row in 0..GRID_ROWS {
+        for col in 0..GRID_COLUMNS {
+            let pos = origin + Vec2::new(col as f32, row as f32) * step;
+            // The top row also gets a shimmering gold foil cover
+            let (backend, brush, cover) = if row == GRID_ROWS - 1 {
+                (
+                    ScratchBackend::Gpu,
+                    ScratchBrush::default(),
+                    ScratchCover {
+                        tint: Color::srgb(1.0, 0.85, 0.45),
+                        shimmer: 0.35,
+                        ..default()
+                    },
+                )
+            } else {
+                (
+                    ScratchBackend::Cpu,
+                    brushes[col as usize % brushes.len()].clone(),
+                    ScratchCover::default(),
+                )
+            };
+            let mut card = commands.spawn((
+                ScratchCard {
+                    size: CARD_SIZE,
+                    // The GPU row scratches a half resolution mask
+                    mask_scale: if backend == ScratchBackend::Gpu {
+                        0.5
+                    } else {
+                        1.0
+                    },
+                    backend,
+                    brush,
+                    cover,
+                    // Holes look cut into the cover
+                    edge: ScratchEdge {
+                        rim_darkness: 0.5,
+                        highlight: 0.4,
+                        shadow: 0.35,
+                        ..default()
+                    },
+                    reveal_thresholds: vec![AUTO_REVEAL_THRESHOLD],
+                    ..ScratchCard::new(reveal.clone(), star.clone())
+                },
+                // Alternate a slight tilt, hit-testing follows each card's transform
+                Transform::from_translation(pos.extend(0.0)).with_rotation(Quat::from_rotation_z(
+                    CARD_TILT * if col % 2 == 0 { 1.0 } else { -1.0 },
+                )),
+                ScratchDebris::default(),
+                ScratchHistory::default(),
+            ));
+            // Bottom row cards hide a seeded draw of symbols, top row cards report their centre
+            if row == GRID_ROWS - 1 {
+                card.insert(ScratchRegions(vec![ScratchRegion::polygon(
+                    "centre",
+                    vec![
+                        Vec2::new(0.5, 0.3),
+                        Vec2::new(0.7, 0.5),
+                        Vec2::new(0.5, 0.7),
+                        Vec2::new(0.3, 0.5),
+                    ],
+                    0.8,
+                )]));
+            } else {
+                card.insert(ScratchCardLayout::new(
+                    symbols.clone(),
+                    slots.clone(),
+                    PrizeOutcome::Seeded(col as u64),
+                ));
+            }
+        }
+    }
+}
 
-/// Handle mouse input and update material
-fn handle_mouse_input(
-    windows: Query<&Window, With<PrimaryWindow>>,
-    buttons: Res<ButtonInput<MouseButton>>,
-    mut scratch_res: ResMut<ScratchResources>,
-    camera_q: Query<(&Camera, &GlobalTransform)>,
-    mut images: ResMut<Assets<Image>>,
-    mut materials: ResMut<Assets<ScratchCardMaterial>>,
-    mut quad_query: Query<&mut MeshMaterial2d<ScratchCardMaterial>, With<ScratchQuad>>,
+/// Dissolve the rest of a card once most of it has been scratched
+fn auto_reveal(
+    mut commands: Commands,
+    mut events: EventReader<ScratchThresholdReached>,
+    keys: Res<ButtonInput<KeyCode>>,
+    cards: Query<Entity, (With<ScratchCard>, Without<ScratchRevealAnimation>)>,
 ) {
-    if !buttons.pressed(MouseButton::Left) {
-        return;
+    for event in events.read() {
+        if cards.contains(event.card) {
+            let style = match event.card.index() % 3 {
+                0 => ScratchRevealStyle::Fade,
+                1 => ScratchRevealStyle::RadialWipe {
+                    origin: Vec2::splat(0.5),
+                },
+                _ => ScratchRevealStyle::NoiseDissolve { scale: 12.0 },
+            };
+            commands
+                .entity(event.card)
+                .insert(ScratchRevealAnimation::new(style, REVEAL_DURATION));
+        }
     }
 
-    let Ok(window) = windows.single() else {
-        return;
-    };
-
-    let Ok((camera, cam_tf)) = camera_q.single() else {
-        return;
-    };
-
-    let Some(cursor_pos) = window.cursor_position() else {
-        return;
-    };
-
-    // Convert coordinates
-    if let Ok(world) = camera.viewport_to_world(cam_tf, cursor_pos) {
-        let pos = world.origin.truncate();
-        let uv = (pos + Vec2::splat(256.0)) / 512.0;
-
-        if uv.x >= 0.0 && uv.x <= 1.0 && uv.y >= 0.0 && uv.y <= 1.0 {
-            // Update mask data
-            let size = scratch_res.size as i32;
-            let px = (uv.x * size as f32) as i32;
-            let py = ((1.0 - uv.y) * size as f32) as i32;
-
-            let mut changed = false;
-
-            for dy in -BRUSH_RADIUS..=BRUSH_RADIUS {
-                for dx in -BRUSH_RADIUS..=BRUSH_RADIUS {
-                    if dx * dx + dy * dy > BRUSH_RADIUS * BRUSH_RADIUS {
-                        continue;
-                    }
-                    let x = px + dx;
-                    let y = py + dy;
-                    if x >= 0 && x < size && y >= 0 && y < size {
-                        let idx = (y * size + x) as usize;
-                        if scratch_res.mask_data[idx] != 255 {
-                            scratch_res.mask_data[idx] = 255;
-                            changed = true;
-                        }
-                    }
-                }
-            }
-
-            if changed {
-                // println!("Updating mask at UV: {:?}", uv); // Optional debug output
-
-                // Create new mask image
-                let new_mask =
-                    images.add(create_mask_image(&scratch_res.mask_data, scratch_res.size));
-
-                // Create new material
-                let new_material = materials.add(ScratchCardMaterial {
-                    reveal_texture: scratch_res.reveal_texture.clone(),
-                    scratch_mask: new_mask,
-                    cover_layer: scratch_res.cover_texture.clone(),
-                });
+    // Space uncovers every card
+    if keys.just_pressed(KeyCode::Space) {
+        for card in &cards {
+            commands.entity(card).insert(ScratchRevealAnimation::new(
+                ScratchRevealStyle::RadialWipe {
+                    origin: Vec2::splat(0.5),
+                },
+                REVEAL_DURATION,
+            ));
+        }
+    }
+}
 
-                // Update quad's material
-                if let Ok(mut material_handle) = quad_query.single_mut() {
-                    material_handle.0 = new_material;
-                }
-            }
+/// Z undoes and Y redoes the last stroke on every card
+fn undo_redo(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut cards: Query<&mut ScratchMask, With<ScratchHistory>>,
+) {
+    for mut mask in &mut cards {
+        if keys.just_pressed(KeyCode::KeyZ) {
+            mask.undo();
+        } else if keys.just_pressed(KeyCode::KeyY) {
+            mask.redo();
         }
     }
 }
 
-/// Create mask image
-fn create_mask_image(data: &[u8], size: u32) -> Image {
-    let mut img = Image::new(
-        Extent3d {
-            width: size,
-            height: size,
-            depth_or_array_layers: 1,
-        },
-        TextureDimension::D2,
-        data.to_vec(),
-        TextureFormat::R8Unorm,
-        RenderAssetUsages::all(),
-    );
+fn announce_matches(mut events: EventReader<PrizeMatched>, layouts: Query<&ScratchCardLayout>) {
+    for event in events.read() {
+        if let Ok(layout) = layouts.get(event.card) {
+            info!(
+                "Card {} matched three {} symbols",
+                event.card, layout.symbols[event.symbol].name
+            );
+        }
+    }
+}
 
-    img.texture_descriptor.usage = TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST;
-    img.sampler = ImageSampler::nearest();
-    img
+fn announce_regions(mut events: EventReader<ScratchRegionRevealed>) {
+    for event in events.read() {
+        info!("Card {} uncovered its {} region", event.card, event.name);
+    }
 }
@@ -0,0 +1,102 @@
+use bevy::prelude::*;
+use mask_2d::sprite_mask::{SpriteMask, SpriteMaskPlugin, SpriteMaskShape};
+
+const VIEW_SIZE: Vec2 = Vec2::new(320.0, 240.0);
+const PORTHOLE_SIZE: f32 = 200.0;
+const SCROLL_SPEED: f32 = 60.0;
+const ROW_SPACING: f32 = 70.0;
+const ROWS: usize = 8;
+const ORBIT_SPEED: f32 = 0.8;
+
+/// Row of the scroll view, moves up and wraps around below the view
+#[derive(Component)]
+struct ScrollRow(usize);
+
+/// Circles the centre of its parent
+#[derive(Component)]
+struct Orbit {
+    radius: f32,
+}
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, SpriteMaskPlugin))
+        .add_systems(Startup, setup)
+        .add_systems(Update, (scroll_rows, orbit))
+        .run();
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn(Camera2d);
+    let ferris = asset_server.load("images/ferris.png");
+    let icon = asset_server.load("images/icon.png");
+
+    // A scroll view: rows of sprites clipped to a rectangle
+    commands
+        .spawn((
+            SpriteMask::new(SpriteMaskShape::Rect, VIEW_SIZE),
+            Transform::from_xyz(-200.0, 0.0, 0.0),
+        ))
+        .with_children(|view| {
+            for row in 0..ROWS {
+                view.spawn((
+                    Sprite {
+                        image: if row % 2 == 0 {
+                            ferris.clone()
+                        } else {
+                            icon.clone()
+                        },
+                        custom_size: Some(Vec2::splat(ROW_SPACING - 10.0)),
+                        ..default()
+                    },
+                    ScrollRow(row),
+                ));
+            }
+        });
+
+    // A round porthole over a large sprite, with a smaller porthole circling inside it
+    commands
+        .spawn((
+            SpriteMask::new(SpriteMaskShape::Ellipse, Vec2::splat(PORTHOLE_SIZE)),
+            Transform::from_xyz(200.0, 0.0, 0.0),
+        ))
+        .with_children(|porthole| {
+            porthole.spawn(Sprite {
+                image: asset_server.load("images/prize.png"),
+                custom_size: Some(Vec2::splat(PORTHOLE_SIZE * 1.5)),
+                ..default()
+            });
+            porthole
+                .spawn((
+                    SpriteMask::new(SpriteMaskShape::Ellipse, Vec2::splat(PORTHOLE_SIZE * 0.4)),
+                    Transform::from_xyz(0.0, 0.0, 1.0),
+                    // Partly leaves the big porthole, which clips it in turn
+                    Orbit {
+                        radius: PORTHOLE_SIZE * 0.45,
+                    },
+                ))
+                .with_child(Sprite {
+                    image: ferris,
+                    custom_size: Some(Vec2::splat(PORTHOLE_SIZE * 0.6)),
+                    ..default()
+                });
+        });
+}
+
+fn scroll_rows(time: Res<Time>, mut rows: Query<(&mut Transform, &ScrollRow)>) {
+    let height = ROWS as f32 * ROW_SPACING;
+    let scrolled = time.elapsed_secs() * SCROLL_SPEED;
+    for (mut transform, row) in &mut rows {
+        let y = (scrolled - row.0 as f32 * ROW_SPACING).rem_euclid(height);
+        transform.translation.y = y - height * 0.5;
+    }
+}
+
+fn orbit(time: Res<Time>, mut orbits: Query<(&mut Transform, &Orbit)>) {
+    let direction = Vec2::from_angle(time.elapsed_secs() * ORBIT_SPEED);
+    for (mut transform, orbit) in &mut orbits {
+        let position = direction * orbit.radius;
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+}
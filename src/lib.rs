@@ -0,0 +1,4 @@
+pub mod mask2d;
+pub mod mask_history;
+pub mod scratch_card;
+pub mod sprite_mask;
@@ -0,0 +1,568 @@
+//! Sprite-style quads whose alpha comes from a separate mask texture: spawn a
+//! [`Mesh2d`] with a [`MeshMaterial2d`] of [`Mask2DMaterial`] after adding the [`Mask2DPlugin`].
+
+use bevy::{
+    ecs::system::{
+        SystemParamItem,
+        lifetimeless::{SRes, SResMut},
+    },
+    math::Affine2,
+    platform::collections::HashMap,
+    prelude::*,
+    render::{
+        RenderApp,
+        mesh::MeshVertexBufferLayoutRef,
+        render_asset::RenderAssets,
+        render_resource::{
+            AddressMode, AsBindGroup, AsBindGroupError, BindGroupLayout, BindGroupLayoutEntries,
+            BindGroupLayoutEntry, BindingResources, BlendState, BufferInitDescriptor, BufferUsages,
+            FilterMode, OwnedBindingResource, RenderPipelineDescriptor, Sampler,
+            SamplerBindingType, SamplerDescriptor, ShaderRef, ShaderStages,
+            SpecializedMeshPipelineError, TextureSampleType, TextureViewDimension,
+            UnpreparedBindGroup,
+            binding_types::{sampler, texture_2d, uniform_buffer},
+            encase::UniformBuffer,
+        },
+        renderer::RenderDevice,
+        texture::GpuImage,
+    },
+    sprite::{AlphaMode2d, Material2d, Material2dKey, Material2dPlugin},
+};
+
+use mask_2d_uniform::Mask2DUniform;
+
+const MASK_2D_SHADER_PATH: &str = "shaders/mask2d.wgsl";
+/// Smallest magnitude of a [`MaskTransform::scale`] component
+pub const MIN_MASK_SCALE: f32 = 1e-4;
+/// Rec. 709 luminance, as in `mask2d.wgsl`
+const LUMINANCE_WEIGHTS: Vec3 = Vec3::new(0.2126, 0.7152, 0.0722);
+
+pub struct Mask2DPlugin;
+
+impl Plugin for Mask2DPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(Material2dPlugin::<Mask2DMaterial>::default());
+        if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.init_resource::<MaskSamplers>();
+        }
+    }
+}
+
+/// Which part of the mask texture's colour is used as the mask value
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MaskChannel {
+    Red,
+    Green,
+    Blue,
+    #[default]
+    Alpha,
+    /// Rec. 709 luminance of the colour, for grayscale masks
+    Luminance,
+}
+
+/// How the mask value is combined with the main texture's own alpha
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MaskCombine {
+    /// The mask value is the alpha, the main texture's is ignored
+    Replace,
+    /// Keeps the main texture's transparent borders
+    #[default]
+    Multiply,
+    Min,
+    /// The mask cuts holes into the main texture
+    Subtract,
+}
+
+/// Turns the mask into a cutout: values below `cutoff` are hidden, values above shown
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaskThreshold {
+    pub cutoff: f32,
+    /// Width of the smoothstep around `cutoff`, `0` for a hard edge
+    pub softness: f32,
+}
+
+impl MaskThreshold {
+    pub fn new(cutoff: f32) -> Self {
+        Self {
+            cutoff,
+            softness: 0.0,
+        }
+    }
+}
+
+/// What the mask shows outside `0..1` UV, e.g. once [`MaskTransform`] moves it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum MaskAddressMode {
+    /// The edge pixels stretch outwards
+    #[default]
+    ClampToEdge,
+    Repeat,
+    /// Repeats with every other copy flipped
+    MirrorRepeat,
+}
+
+impl From<MaskAddressMode> for AddressMode {
+    fn from(mode: MaskAddressMode) -> Self {
+        match mode {
+            MaskAddressMode::ClampToEdge => Self::ClampToEdge,
+            MaskAddressMode::Repeat => Self::Repeat,
+            MaskAddressMode::MirrorRepeat => Self::MirrorRepeat,
+        }
+    }
+}
+
+/// How the mask is filtered between its pixels
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum MaskFilter {
+    /// Smooth edges
+    #[default]
+    Linear,
+    /// Hard pixel-art edges
+    Nearest,
+}
+
+impl From<MaskFilter> for FilterMode {
+    fn from(filter: MaskFilter) -> Self {
+        match filter {
+            MaskFilter::Linear => Self::Linear,
+            MaskFilter::Nearest => Self::Nearest,
+        }
+    }
+}
+
+/// How a [`Mask2DMaterial`] samples its mask, independent of the main texture and of
+/// the mask image's own sampler, so materials sharing a mask can sample it differently
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct MaskSampler {
+    pub address_mode: MaskAddressMode,
+    pub filter: MaskFilter,
+}
+
+impl MaskSampler {
+    fn descriptor(&self) -> SamplerDescriptor<'static> {
+        let address_mode = self.address_mode.into();
+        let filter = self.filter.into();
+        SamplerDescriptor {
+            label: Some("mask_sampler"),
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_filter: filter,
+            ..default()
+        }
+    }
+}
+
+/// GPU samplers of every [`MaskSampler`] in use, shared by the materials
+#[derive(Resource, Default)]
+pub struct MaskSamplers(HashMap<MaskSampler, Sampler>);
+
+/// Placement of the mask on the quad, in UV; rotation and scale are about the mask's centre
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaskTransform {
+    /// Shift of the mask across the quad
+    pub offset: Vec2,
+    /// Size of the mask relative to the quad, components closer to zero than
+    /// [`MIN_MASK_SCALE`] are pushed out to it
+    pub scale: Vec2,
+    /// Counter-clockwise on screen, in radians
+    pub rotation: f32,
+    /// Width over height of the quad, so rotating doesn't shear the mask on non-square quads
+    pub aspect: f32,
+}
+
+impl Default for MaskTransform {
+    fn default() -> Self {
+        Self {
+            offset: Vec2::ZERO,
+            scale: Vec2::ONE,
+            rotation: 0.0,
+            aspect: 1.0,
+        }
+    }
+}
+
+impl MaskTransform {
+    /// Maps a quad UV to the mask UV drawn there
+    pub fn quad_to_mask(&self) -> Affine2 {
+        let center = Vec2::splat(0.5);
+        // A zero scale would collapse the mask and leave nothing to invert
+        let scale = self.scale.signum() * self.scale.abs().max(Vec2::splat(MIN_MASK_SCALE));
+        // Rotate in the quad's own proportions, where a turn keeps its angles
+        let aspect = Vec2::new(self.aspect.max(MIN_MASK_SCALE), 1.0);
+        // UV runs y down, so a counter-clockwise turn on screen is clockwise in UV
+        let mask_to_quad = Affine2::from_translation(center + self.offset)
+            * Affine2::from_scale(aspect.recip())
+            * Affine2::from_angle(-self.rotation)
+            * Affine2::from_scale(aspect * scale)
+            * Affine2::from_translation(-center);
+        mask_to_quad.inverse()
+    }
+}
+
+/// Material drawing `main_texture` with its alpha taken from `mask_texture`.
+///
+/// The main texture is sampled with its image's own sampler, the mask with `mask_sampler`.
+#[derive(Asset, TypePath, Debug, Clone)]
+pub struct Mask2DMaterial {
+    pub main_texture: Handle<Image>,
+    pub mask_texture: Handle<Image>,
+    pub mask_sampler: MaskSampler,
+    pub channel: MaskChannel,
+    /// Show the quad where the mask is empty instead
+    pub invert: bool,
+    /// Applied after `invert`, the mask value is used as is without one
+    pub threshold: Option<MaskThreshold>,
+    /// Change it from a system to move, scale or spin the mask over the quad
+    pub mask_transform: MaskTransform,
+    pub combine: MaskCombine,
+    /// Set when `main_texture` already has premultiplied alpha, e.g. a camera's render target
+    pub premultiplied_main: bool,
+}
+
+impl Mask2DMaterial {
+    /// Mask taken from the alpha channel, as is
+    pub fn new(main_texture: Handle<Image>, mask_texture: Handle<Image>) -> Self {
+        Self {
+            main_texture,
+            mask_texture,
+            mask_sampler: MaskSampler::default(),
+            channel: MaskChannel::Alpha,
+            invert: false,
+            threshold: None,
+            mask_transform: MaskTransform::default(),
+            combine: MaskCombine::Multiply,
+            premultiplied_main: false,
+        }
+    }
+
+    /// Mask value in `0..=1` for a mask texel, after channel selection, inversion and threshold
+    pub fn mask_value(&self, mask: Vec4) -> f32 {
+        let mut value = match self.channel {
+            MaskChannel::Red => mask.x,
+            MaskChannel::Green => mask.y,
+            MaskChannel::Blue => mask.z,
+            MaskChannel::Alpha => mask.w,
+            MaskChannel::Luminance => mask.truncate().dot(LUMINANCE_WEIGHTS),
+        };
+        if self.invert {
+            value = 1.0 - value;
+        }
+        if let Some(threshold) = self.threshold {
+            let half_width = threshold.softness * 0.5;
+            value = if half_width <= 0.0 {
+                (value >= threshold.cutoff) as u32 as f32
+            } else {
+                smoothstep(
+                    threshold.cutoff - half_width,
+                    threshold.cutoff + half_width,
+                    value,
+                )
+            };
+        }
+        value
+    }
+
+    /// Premultiplied colour `mask2d.wgsl` outputs for a main texture texel and a mask texel,
+    /// both as sampled (linear, straight alpha unless [`Mask2DMaterial::premultiplied_main`]).
+    ///
+    /// ```
+    /// use bevy::prelude::*;
+    /// use mask_2d::mask2d::{Mask2DMaterial, MaskCombine};
+    ///
+    /// let mut material = Mask2DMaterial::new(Handle::default(), Handle::default());
+    /// let main = Vec4::new(1.0, 0.5, 0.0, 0.5);
+    /// let mask = Vec4::new(0.0, 0.0, 0.0, 0.5);
+    /// assert_eq!(material.reference_pixel(main, mask), Vec4::new(0.25, 0.125, 0.0, 0.25));
+    ///
+    /// material.combine = MaskCombine::Replace;
+    /// assert_eq!(material.reference_pixel(main, mask).w, 0.5);
+    /// ```
+    pub fn reference_pixel(&self, main: Vec4, mask: Vec4) -> Vec4 {
+        let mask = self.mask_value(mask);
+        let alpha = match self.combine {
+            MaskCombine::Replace => mask,
+            MaskCombine::Multiply => main.w * mask,
+            MaskCombine::Min => main.w.min(mask),
+            MaskCombine::Subtract => (main.w - mask).max(0.0),
+        };
+        let color = match self.premultiplied_main {
+            true if main.w > 0.0 => main.truncate() / main.w,
+            true => Vec3::ZERO,
+            false => main.truncate(),
+        };
+        (color * alpha).extend(alpha)
+    }
+}
+
+impl Mask2DMaterial {
+    /// Settings for `mask2d.wgsl`
+    fn uniform(&self) -> Mask2DUniform {
+        let threshold = self.threshold.unwrap_or(MaskThreshold::new(0.0));
+        let quad_to_mask = self.mask_transform.quad_to_mask();
+        Mask2DUniform {
+            uv_matrix: quad_to_mask.matrix2,
+            uv_translation: quad_to_mask.translation,
+            channel: match self.channel {
+                MaskChannel::Red => 0,
+                MaskChannel::Green => 1,
+                MaskChannel::Blue => 2,
+                MaskChannel::Alpha => 3,
+                MaskChannel::Luminance => 4,
+            },
+            invert: self.invert as u32,
+            threshold: self.threshold.is_some() as u32,
+            cutoff: threshold.cutoff,
+            softness: threshold.softness.max(0.0),
+            combine: match self.combine {
+                MaskCombine::Replace => 0,
+                MaskCombine::Multiply => 1,
+                MaskCombine::Min => 2,
+                MaskCombine::Subtract => 3,
+            },
+            premultiplied_main: self.premultiplied_main as u32,
+        }
+    }
+}
+
+/// Bindings as in `mask2d.wgsl`; written out by hand because the derive can only bind
+/// an image's own sampler, not one built from [`Mask2DMaterial::mask_sampler`]
+impl AsBindGroup for Mask2DMaterial {
+    type Data = ();
+    type Param = (SRes<RenderAssets<GpuImage>>, SResMut<MaskSamplers>);
+
+    fn label() -> Option<&'static str> {
+        Some("mask_2d_material")
+    }
+
+    fn unprepared_bind_group(
+        &self,
+        _layout: &BindGroupLayout,
+        render_device: &RenderDevice,
+        (images, samplers): &mut SystemParamItem<'_, '_, Self::Param>,
+        _force_no_bindless: bool,
+    ) -> Result<UnpreparedBindGroup<Self::Data>, AsBindGroupError> {
+        let (Some(main), Some(mask)) = (
+            images.get(&self.main_texture),
+            images.get(&self.mask_texture),
+        ) else {
+            return Err(AsBindGroupError::RetryNextUpdate);
+        };
+        let mask_sampler = samplers
+            .0
+            .entry(self.mask_sampler)
+            .or_insert_with(|| render_device.create_sampler(&self.mask_sampler.descriptor()))
+            .clone();
+
+        let mut settings = UniformBuffer::new(Vec::new());
+        settings.write(&self.uniform()).unwrap();
+        let settings = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("mask_2d_settings"),
+            contents: settings.as_ref(),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let filtering = SamplerBindingType::Filtering;
+        Ok(UnpreparedBindGroup {
+            bindings: BindingResources(vec![
+                (
+                    0,
+                    OwnedBindingResource::TextureView(
+                        TextureViewDimension::D2,
+                        main.texture_view.clone(),
+                    ),
+                ),
+                (
+                    1,
+                    OwnedBindingResource::Sampler(filtering, main.sampler.clone()),
+                ),
+                (
+                    2,
+                    OwnedBindingResource::TextureView(
+                        TextureViewDimension::D2,
+                        mask.texture_view.clone(),
+                    ),
+                ),
+                (3, OwnedBindingResource::Buffer(settings)),
+                (4, OwnedBindingResource::Sampler(filtering, mask_sampler)),
+            ]),
+            data: (),
+        })
+    }
+
+    fn bind_group_layout_entries(
+        _render_device: &RenderDevice,
+        _force_no_bindless: bool,
+    ) -> Vec<BindGroupLayoutEntry> {
+        let texture = texture_2d(TextureSampleType::Float { filterable: true });
+        let filtering = sampler(SamplerBindingType::Filtering);
+        BindGroupLayoutEntries::with_indices(
+            ShaderStages::VERTEX_FRAGMENT,
+            (
+                (0, texture),
+                (1, filtering),
+                (2, texture),
+                (3, uniform_buffer::<Mask2DUniform>(false)),
+                (4, filtering),
+            ),
+        )
+        .to_vec()
+    }
+}
+
+impl Material2d for Mask2DMaterial {
+    fn fragment_shader() -> ShaderRef {
+        MASK_2D_SHADER_PATH.into()
+    }
+
+    fn vertex_shader() -> ShaderRef {
+        ShaderRef::Default
+    }
+
+    fn alpha_mode(&self) -> AlphaMode2d {
+        AlphaMode2d::Blend
+    }
+
+    /// The shader outputs premultiplied alpha, so the transparent pass must blend it as such
+    fn specialize(
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayoutRef,
+        _key: Material2dKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        if let Some(fragment) = &mut descriptor.fragment {
+            for target in fragment.targets.iter_mut().flatten() {
+                target.blend = Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// GLSL/WGSL `smoothstep`
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+// The derive's layout checks sit beside the uniform and are never called
+#[allow(dead_code)]
+mod mask_2d_uniform {
+    use bevy::{prelude::*, render::render_resource::ShaderType};
+
+    /// Mask settings as seen by `mask2d.wgsl`, built from [`super::Mask2DMaterial`]
+    #[derive(ShaderType, Debug, Clone, Copy)]
+    pub struct Mask2DUniform {
+        /// Quad UV to mask UV, see [`super::MaskTransform::quad_to_mask`]
+        pub uv_matrix: Mat2,
+        pub uv_translation: Vec2,
+        /// 0..=3 = red, green, blue, alpha, 4 = luminance
+        pub channel: u32,
+        pub invert: u32,
+        pub threshold: u32,
+        pub cutoff: f32,
+        pub softness: f32,
+        /// 0 = replace, 1 = multiply, 2 = min, 3 = subtract
+        pub combine: u32,
+        pub premultiplied_main: u32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn material(combine: MaskCombine) -> Mask2DMaterial {
+        Mask2DMaterial {
+            combine,
+            ..Mask2DMaterial::new(Handle::default(), Handle::default())
+        }
+    }
+
+    /// Alpha of a white texel with `main` alpha under a mask with `mask` alpha
+    fn alpha(material: &Mask2DMaterial, main: f32, mask: f32) -> f32 {
+        material
+            .reference_pixel(
+                Vec4::new(1.0, 1.0, 1.0, main),
+                Vec4::new(0.0, 0.0, 0.0, mask),
+            )
+            .w
+    }
+
+    #[test]
+    fn combine_modes() {
+        let replace = material(MaskCombine::Replace);
+        assert_eq!(alpha(&replace, 0.2, 0.5), 0.5);
+        let multiply = material(MaskCombine::Multiply);
+        assert_eq!(alpha(&multiply, 0.5, 0.5), 0.25);
+        let min = material(MaskCombine::Min);
+        assert_eq!(alpha(&min, 0.8, 0.5), 0.5);
+        assert_eq!(alpha(&min, 0.25, 0.5), 0.25);
+        let subtract = material(MaskCombine::Subtract);
+        assert!((alpha(&subtract, 0.75, 0.5) - 0.25).abs() < 1e-6);
+        assert_eq!(alpha(&subtract, 0.25, 0.5), 0.0);
+    }
+
+    #[test]
+    fn invert_then_threshold() {
+        let mut material = material(MaskCombine::Replace);
+        material.invert = true;
+        material.threshold = Some(MaskThreshold::new(0.5));
+        assert_eq!(alpha(&material, 1.0, 0.3), 1.0);
+        assert_eq!(alpha(&material, 1.0, 0.7), 0.0);
+
+        material.threshold = Some(MaskThreshold {
+            cutoff: 0.5,
+            softness: 0.2,
+        });
+        // Inverted to 0.55, three quarters of the way across the soft edge
+        assert!((alpha(&material, 1.0, 0.45) - 0.84375).abs() < 1e-5);
+    }
+
+    #[test]
+    fn premultiplied_main_is_not_multiplied_twice() {
+        let mut material = material(MaskCombine::Multiply);
+        material.premultiplied_main = true;
+        let main = Vec4::new(0.5, 0.25, 0.0, 0.5);
+        let opaque = Vec4::new(0.0, 0.0, 0.0, 1.0);
+        assert_eq!(material.reference_pixel(main, opaque), main);
+        let half = Vec4::new(0.0, 0.0, 0.0, 0.5);
+        assert_eq!(
+            material.reference_pixel(main, half),
+            Vec4::new(0.25, 0.125, 0.0, 0.25)
+        );
+        // Fully transparent texels carry no colour to recover
+        assert_eq!(material.reference_pixel(Vec4::ZERO, opaque), Vec4::ZERO);
+
+        material.premultiplied_main = false;
+        assert_eq!(
+            material.reference_pixel(main, opaque),
+            Vec4::new(0.25, 0.125, 0.0, 0.5)
+        );
+    }
+
+    #[test]
+    fn rotation_keeps_its_angles_on_wide_quads() {
+        let transform = MaskTransform {
+            rotation: std::f32::consts::FRAC_PI_2,
+            aspect: 2.0,
+            ..default()
+        };
+        let quad_to_mask = transform.quad_to_mask();
+        // A quarter of the height above the centre shows what lay as far to its right before
+        // turning, which on a quad twice as wide is only an eighth of its width
+        let above = quad_to_mask.transform_point2(Vec2::new(0.5, 0.25));
+        assert!(above.abs_diff_eq(Vec2::new(0.625, 0.5), 1e-5), "{above}");
+        let right = quad_to_mask.transform_point2(Vec2::new(0.75, 0.5));
+        assert!(right.abs_diff_eq(Vec2::new(0.5, 1.0), 1e-5), "{right}");
+    }
+
+    #[test]
+    fn zero_scale_stays_invertible() {
+        let transform = MaskTransform {
+            scale: Vec2::ZERO,
+            ..default()
+        };
+        assert!(transform.quad_to_mask().is_finite());
+    }
+}
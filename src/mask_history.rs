@@ -0,0 +1,281 @@
+//! Undo and redo for edits to mask pixel buffers
+
+use bevy::prelude::*;
+
+/// Undo steps kept by default before the oldest is dropped
+pub const DEFAULT_HISTORY_LIMIT: usize = 64;
+
+/// Contents of a rectangle before and after one batch of edits
+#[derive(Debug, Clone)]
+struct MaskPatch {
+    /// Pixels, max exclusive
+    rect: URect,
+    before: Vec<u8>,
+    after: Vec<u8>,
+}
+
+/// One undo step, non-overlapping patches and the rectangle around them
+#[derive(Debug, Clone)]
+struct MaskEdit {
+    rect: URect,
+    patches: Vec<MaskPatch>,
+}
+
+/// Undo and redo over any row-major pixel buffer, e.g. a scratch card mask or an [`Image`]'s data.
+///
+/// Call [`MaskHistory::before_edit`] with each rectangle about to change, then
+/// [`MaskHistory::commit`] to store what those rectangles held as one undo step.
+/// Only the edited area is ever copied, each pixel once per step.
+///
+/// ```
+/// use bevy::math::URect;
+/// use mask_2d::mask_history::MaskHistory;
+///
+/// let mut data = vec![0u8; 16 * 16];
+/// let mut history = MaskHistory::new(16, 1);
+///
+/// history.before_edit(&data, URect::new(3, 5, 4, 6));
+/// data[5 * 16 + 3] = 255;
+/// history.commit(&data);
+///
+/// history.undo(&mut data);
+/// assert!(data.iter().all(|&value| value == 0));
+/// history.redo(&mut data);
+/// assert_eq!(data[5 * 16 + 3], 255);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MaskHistory {
+    width: u32,
+    bytes_per_pixel: u32,
+    /// Non-overlapping areas announced since the last commit and what they held before
+    pending: Vec<(URect, Vec<u8>)>,
+    undo: Vec<MaskEdit>,
+    redo: Vec<MaskEdit>,
+    /// Undo steps kept before the oldest is dropped
+    pub limit: usize,
+}
+
+impl MaskHistory {
+    /// Empty history of a buffer `width` pixels per row of `bytes_per_pixel` each
+    pub fn new(width: u32, bytes_per_pixel: u32) -> Self {
+        Self {
+            width,
+            bytes_per_pixel,
+            pending: Vec::new(),
+            undo: Vec::new(),
+            redo: Vec::new(),
+            limit: DEFAULT_HISTORY_LIMIT,
+        }
+    }
+
+    /// Forget every step, including edits not committed yet
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty() || !self.pending.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Remember what a rectangle (max exclusive) of `data` holds before it is edited.
+    ///
+    /// Every edit between two commits must be announced, the area outside the
+    /// announced rectangles is assumed unchanged. Parts announced before are not copied again.
+    pub fn before_edit(&mut self, data: &[u8], rect: URect) {
+        let rect = rect.intersect(self.bounds(data));
+        if rect.is_empty() {
+            return;
+        }
+        // Recent edits are the likeliest to overlap, e.g. the previous stamp of a stroke
+        let mut uncovered = vec![rect];
+        for (covered, _) in self.pending.iter().rev() {
+            uncovered = uncovered
+                .into_iter()
+                .flat_map(|piece| subtract(piece, *covered))
+                .collect();
+            if uncovered.is_empty() {
+                return;
+            }
+        }
+        for piece in uncovered {
+            let before = self.copy_rect(data, piece);
+            self.pending.push((piece, before));
+        }
+    }
+
+    /// Store the edits announced since the last commit as one step, returns its rectangle.
+    ///
+    /// Nothing is stored if the announced area ended up unchanged.
+    pub fn commit(&mut self, data: &[u8]) -> Option<URect> {
+        let patches = std::mem::take(&mut self.pending)
+            .into_iter()
+            .filter_map(|(rect, before)| {
+                let after = self.copy_rect(data, rect);
+                (after != before).then_some(MaskPatch {
+                    rect,
+                    before,
+                    after,
+                })
+            })
+            .collect::<Vec<_>>();
+        let rect = patches
+            .iter()
+            .map(|patch| patch.rect)
+            .reduce(|rect, patch| rect.union(patch))?;
+
+        self.redo.clear();
+        self.undo.push(MaskEdit { rect, patches });
+        if self.undo.len() > self.limit {
+            self.undo.remove(0);
+        }
+        Some(rect)
+    }
+
+    /// Revert the last step in `data`, committing pending edits first, returns the rectangle to re-upload
+    pub fn undo(&mut self, data: &mut [u8]) -> Option<URect> {
+        self.commit(data);
+        let edit = self.undo.pop()?;
+        for patch in &edit.patches {
+            self.apply(data, patch.rect, &patch.before);
+        }
+        let rect = edit.rect;
+        self.redo.push(edit);
+        Some(rect)
+    }
+
+    /// Re-apply the last undone step in `data`, returns the rectangle to re-upload
+    pub fn redo(&mut self, data: &mut [u8]) -> Option<URect> {
+        self.commit(data);
+        let edit = self.redo.pop()?;
+        for patch in &edit.patches {
+            self.apply(data, patch.rect, &patch.after);
+        }
+        let rect = edit.rect;
+        self.undo.push(edit);
+        Some(rect)
+    }
+
+    /// Write the contents of `rect` back into `data`
+    fn apply(&self, data: &mut [u8], rect: URect, contents: &[u8]) {
+        let stride = (self.width * self.bytes_per_pixel) as usize;
+        let start = (rect.min.x * self.bytes_per_pixel) as usize;
+        let len = self.row_len(rect);
+        for (y, bytes) in (rect.min.y..rect.max.y).zip(contents.chunks_exact(len)) {
+            let offset = y as usize * stride + start;
+            data[offset..offset + len].copy_from_slice(bytes);
+        }
+    }
+
+    /// Pixels covered by `data`
+    fn bounds(&self, data: &[u8]) -> URect {
+        let stride = (self.width * self.bytes_per_pixel) as usize;
+        URect::new(0, 0, self.width, (data.len() / stride.max(1)) as u32)
+    }
+
+    fn row_len(&self, rect: URect) -> usize {
+        (rect.width() * self.bytes_per_pixel) as usize
+    }
+
+    fn copy_rect(&self, data: &[u8], rect: URect) -> Vec<u8> {
+        let stride = (self.width * self.bytes_per_pixel) as usize;
+        let start = (rect.min.x * self.bytes_per_pixel) as usize;
+        let len = self.row_len(rect);
+        (rect.min.y as usize..rect.max.y as usize)
+            .flat_map(|y| &data[y * stride + start..][..len])
+            .copied()
+            .collect()
+    }
+}
+
+/// The parts of `rect` outside `cut`, at most four
+fn subtract(rect: URect, cut: URect) -> Vec<URect> {
+    let overlap = rect.intersect(cut);
+    if overlap.is_empty() {
+        return vec![rect];
+    }
+    [
+        // Full-width bands above and below, then the sides of the overlap's rows
+        URect::new(rect.min.x, rect.min.y, rect.max.x, overlap.min.y),
+        URect::new(rect.min.x, overlap.max.y, rect.max.x, rect.max.y),
+        URect::new(rect.min.x, overlap.min.y, overlap.min.x, overlap.max.y),
+        URect::new(overlap.max.x, overlap.min.y, rect.max.x, overlap.max.y),
+    ]
+    .into_iter()
+    .filter(|piece| !piece.is_empty())
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn separate_edits_commit_as_one_step() {
+        let mut data = vec![0u8; 8 * 6 * 2];
+        let mut history = MaskHistory::new(8, 2);
+
+        history.before_edit(&data, URect::new(1, 1, 3, 2));
+        data[(8 + 1) * 2] = 10;
+        history.before_edit(&data, URect::new(5, 4, 7, 6));
+        data[(5 * 8 + 6) * 2 + 1] = 20;
+        // Already covered, must not overwrite the saved contents with edited ones
+        history.before_edit(&data, URect::new(1, 1, 2, 2));
+        let original = {
+            let mut original = data.clone();
+            original[(8 + 1) * 2] = 0;
+            original[(5 * 8 + 6) * 2 + 1] = 0;
+            original
+        };
+        let edited = data.clone();
+
+        assert_eq!(history.commit(&data), Some(URect::new(1, 1, 7, 6)));
+        assert_eq!(history.undo(&mut data), Some(URect::new(1, 1, 7, 6)));
+        assert_eq!(data, original);
+        history.redo(&mut data);
+        assert_eq!(data, edited);
+    }
+
+    #[test]
+    fn overlapping_edits_copy_each_pixel_once() {
+        let mut data = vec![0u8; 8 * 8];
+        let mut history = MaskHistory::new(8, 1);
+        // A diagonal run of overlapping stamps, each painted right after it's announced
+        for step in 0..5 {
+            let rect = URect::new(step, step, step + 3, step + 3);
+            history.before_edit(&data, rect);
+            for y in rect.min.y..rect.max.y {
+                for x in rect.min.x..rect.max.x {
+                    data[(y * 8 + x) as usize] += 1;
+                }
+            }
+        }
+        let copied = history
+            .pending
+            .iter()
+            .map(|(_, before)| before.len())
+            .sum::<usize>();
+        // Nine pixels for the first stamp and five more for each after it
+        assert_eq!(copied, 9 + 4 * 5);
+
+        let edited = data.clone();
+        assert_eq!(history.undo(&mut data), Some(URect::new(0, 0, 7, 7)));
+        assert!(data.iter().all(|&value| value == 0));
+        history.redo(&mut data);
+        assert_eq!(data, edited);
+    }
+
+    #[test]
+    fn unchanged_edits_store_nothing() {
+        let data = vec![7u8; 4 * 4];
+        let mut history = MaskHistory::new(4, 1);
+        history.before_edit(&data, URect::new(0, 0, 4, 4));
+        assert_eq!(history.commit(&data), None);
+        assert!(!history.can_undo());
+    }
+}
@@ -0,0 +1,125 @@
+use std::sync::Arc;
+
+use bevy::prelude::*;
+
+use super::ScratchStamp;
+
+const DEFAULT_BRUSH_RADIUS: f32 = 20.0;
+const DEFAULT_BRUSH_SPACING: f32 = 0.25;
+
+/// How a stamp combines with the mask value already there
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScratchBlend {
+    /// Keep the larger value, repeated passes never exceed the stamp opacity
+    #[default]
+    Max,
+    /// Add to the existing value, repeated passes build up to fully revealed
+    Additive,
+}
+
+/// Brush used when scratching a card
+#[derive(Debug, Clone)]
+pub struct ScratchBrush {
+    /// Radius in card units, the size of the card's quad before its transform
+    pub radius: f32,
+    /// Fraction of the radius painted at full strength, the rest fades out
+    pub hardness: f32,
+    /// Strength of a single stamp in `0..=1`, scaled by input pressure
+    pub opacity: f32,
+    /// Footprint taken from this image's alpha channel instead of a circle, e.g. a coin
+    pub stamp_image: Option<Handle<Image>>,
+    pub blend: ScratchBlend,
+    /// Distance between interpolated stamps as a fraction of the radius
+    pub spacing: f32,
+    /// Follow a Catmull-Rom spline through the pointer samples instead of straight lines
+    pub smoothing: bool,
+}
+
+impl ScratchBrush {
+    /// Radius in mask pixels for a mask with `mask_scale` pixels per card unit
+    pub fn radius_pixels(&self, mask_scale: f32) -> i32 {
+        (self.radius * mask_scale).round().max(0.0) as i32
+    }
+
+    /// Distance between interpolated stamps in mask pixels
+    pub fn spacing_pixels(&self, mask_scale: f32) -> f32 {
+        (self.radius * mask_scale * self.spacing).max(1.0)
+    }
+
+    /// Stamp centred on a mask pixel position with the given input pressure in `0..=1`
+    pub fn stamp(
+        &self,
+        centre: Vec2,
+        pressure: f32,
+        mask_scale: f32,
+        footprint: Option<Arc<BrushFootprint>>,
+    ) -> ScratchStamp {
+        ScratchStamp {
+            center: centre.floor().as_ivec2(),
+            radius: self.radius_pixels(mask_scale),
+            value: ((self.opacity * pressure).clamp(0.0, 1.0) * 255.0).round() as u8,
+            hardness: self.hardness,
+            blend: self.blend,
+            footprint,
+        }
+    }
+}
+
+impl Default for ScratchBrush {
+    fn default() -> Self {
+        Self {
+            radius: DEFAULT_BRUSH_RADIUS,
+            hardness: 1.0,
+            opacity: 1.0,
+            stamp_image: None,
+            blend: ScratchBlend::Max,
+            spacing: DEFAULT_BRUSH_SPACING,
+            smoothing: false,
+        }
+    }
+}
+
+/// Per-pixel weights of a custom brush, resampled to the stamp's `2 * radius + 1` square
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrushFootprint {
+    pub size: u32,
+    pub weights: Vec<f32>,
+}
+
+impl BrushFootprint {
+    /// Resample an image's alpha channel, `None` if the image has no CPU data
+    pub fn from_image(image: &Image, radius: i32) -> Option<Self> {
+        let size = (2 * radius + 1).max(1) as u32;
+        let width = image.width();
+        let height = image.height();
+        if width == 0 || height == 0 {
+            return None;
+        }
+
+        let mut weights = Vec::with_capacity((size * size) as usize);
+        for y in 0..size {
+            for x in 0..size {
+                // Nearest sample at the centre of each footprint pixel
+                let sx = ((x as f32 + 0.5) / size as f32 * width as f32) as u32;
+                let sy = ((y as f32 + 0.5) / size as f32 * height as f32) as u32;
+                let color = image
+                    .get_color_at(sx.min(width - 1), sy.min(height - 1))
+                    .ok()?;
+                weights.push(color.alpha());
+            }
+        }
+
+        Some(Self { size, weights })
+    }
+
+    /// Weight at an offset from the stamp centre
+    pub fn weight(&self, dx: i32, dy: i32) -> f32 {
+        let half = (self.size / 2) as i32;
+        let x = dx + half;
+        let y = dy + half;
+        if x < 0 || y < 0 || x >= self.size as i32 || y >= self.size as i32 {
+            return 0.0;
+        }
+        self.weights[(y as u32 * self.size + x as u32) as usize]
+    }
+}
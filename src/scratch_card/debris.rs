@@ -0,0 +1,228 @@
+use std::f32::consts::TAU;
+
+use bevy::{
+    prelude::*,
+    render::{primitives::Aabb, view::RenderLayers},
+};
+
+use super::{ScratchCard, ScratchCover, ScratchMask, splitmix64};
+
+/// Debris colour when the cover texture has no readable CPU data
+const FALLBACK_COLOR: Color = Color::srgb(0.7, 0.7, 0.7);
+/// Distance in front of the card so debris isn't hidden behind it
+const DEBRIS_DEPTH: f32 = 1.0;
+
+/// Insert on a card to throw off sprite debris tinted by the cover where it's scratched
+#[derive(Component, Debug, Clone)]
+pub struct ScratchDebris {
+    /// Particles per newly revealed mask pixel
+    pub particles_per_pixel: f32,
+    /// Cap on particles spawned for one card in one frame
+    pub max_per_frame: u32,
+    /// Sprite edge length in world units
+    pub size: f32,
+    /// Seconds a particle lives while fading out
+    pub lifetime: f32,
+    /// Largest initial speed in world units per second
+    pub speed: f32,
+    /// Downward acceleration in world units per second squared
+    pub gravity: f32,
+    /// Stamps that revealed pixels since the last spawn: centre, radius and revealed count
+    pending: Vec<(IVec2, i32, u32)>,
+    /// Fractional particles carried to the next stamp
+    carry: f32,
+    rng: u64,
+}
+
+impl Default for ScratchDebris {
+    fn default() -> Self {
+        Self {
+            particles_per_pixel: 0.02,
+            max_per_frame: 48,
+            size: 3.0,
+            lifetime: 0.7,
+            speed: 160.0,
+            gravity: 600.0,
+            pending: Vec::new(),
+            carry: 0.0,
+            rng: 0,
+        }
+    }
+}
+
+impl ScratchDebris {
+    /// Note a stamp that revealed `revealed` pixels around `center`
+    pub(super) fn record(&mut self, center: IVec2, radius: i32, revealed: u32) {
+        if revealed > 0 {
+            self.pending.push((center, radius, revealed));
+        }
+    }
+
+    /// Uniform random number in `0..1`
+    fn random(&mut self) -> f32 {
+        (splitmix64(&mut self.rng) >> 40) as f32 / (1 << 24) as f32
+    }
+}
+
+/// A single flake of scratched-off cover
+#[derive(Component, Debug, Clone)]
+pub struct ScratchDebrisParticle {
+    pub velocity: Vec3,
+    pub gravity: f32,
+    pub age: f32,
+    pub lifetime: f32,
+}
+
+type DebrisCard = (
+    &'static ScratchCard,
+    &'static GlobalTransform,
+    &'static Aabb,
+    &'static ScratchMask,
+    &'static mut ScratchDebris,
+    Option<&'static RenderLayers>,
+);
+
+/// Turn recorded stamps into debris sprites at the scratched spots
+pub(super) fn spawn_debris(
+    mut commands: Commands,
+    images: Res<Assets<Image>>,
+    mut cards: Query<DebrisCard>,
+) {
+    for (card, card_tf, aabb, mask, mut debris, layers) in &mut cards {
+        if debris.pending.is_empty() {
+            continue;
+        }
+        let cover = images.get(&card.cover_texture);
+        let min = aabb.min().truncate();
+        let max = aabb.max().truncate();
+        let mut budget = debris.max_per_frame;
+
+        for (center, radius, revealed) in std::mem::take(&mut debris.pending) {
+            let wanted = debris.carry + revealed as f32 * debris.particles_per_pixel;
+            debris.carry = wanted.fract();
+            let count = (wanted as u32).min(budget);
+            budget -= count;
+
+            for _ in 0..count {
+                // Somewhere inside the stamp, flying off in the card's plane
+                let angle = debris.random() * TAU;
+                let offset = Vec2::from_angle(angle) * debris.random() * radius as f32;
+                let pixel = center.as_vec2() + 0.5 + offset;
+                let uv = pixel / mask.size.as_vec2();
+                let local = Vec2::new(
+                    min.x + uv.x * (max.x - min.x),
+                    max.y - uv.y * (max.y - min.y),
+                );
+                let position = card_tf.transform_point(local.extend(DEBRIS_DEPTH));
+
+                let direction = Vec2::from_angle(debris.random() * TAU).extend(0.0);
+                let velocity = card_tf
+                    .affine()
+                    .transform_vector3(direction)
+                    .normalize_or_zero()
+                    * debris.speed
+                    * (0.3 + 0.7 * debris.random());
+
+                let color = cover
+                    .and_then(|image| cover_color(image, &card.cover, uv))
+                    .unwrap_or(FALLBACK_COLOR);
+
+                let mut particle = commands.spawn((
+                    Sprite {
+                        color,
+                        custom_size: Some(Vec2::splat(debris.size)),
+                        ..default()
+                    },
+                    Transform::from_translation(position)
+                        .with_rotation(card_tf.compute_transform().rotation),
+                    ScratchDebrisParticle {
+                        velocity,
+                        gravity: debris.gravity,
+                        age: 0.0,
+                        lifetime: debris.lifetime,
+                    },
+                ));
+                // Seen by the same cameras as the card it came off
+                if let Some(layers) = layers {
+                    particle.insert(layers.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Cover colour shown at a card UV, `None` if the image can't be read on the CPU
+fn cover_color(image: &Image, cover: &ScratchCover, uv: Vec2) -> Option<Color> {
+    let size = image.size();
+    let pattern = (uv * cover.tiling + cover.offset).fract_gl();
+    let texel = (pattern * size.as_vec2())
+        .as_uvec2()
+        .min(size.saturating_sub(UVec2::ONE));
+    let color = image
+        .get_color_at(texel.x, texel.y)
+        .ok()?
+        .to_linear()
+        .to_vec4();
+    Some(LinearRgba::from_vec4(color * cover.tint.to_linear().to_vec4()).into())
+}
+
+/// Move, fade and despawn debris
+pub(super) fn update_debris(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut particles: Query<(
+        Entity,
+        &mut Transform,
+        &mut Sprite,
+        &mut ScratchDebrisParticle,
+    )>,
+) {
+    let dt = time.delta_secs();
+    for (entity, mut transform, mut sprite, mut particle) in &mut particles {
+        particle.age += dt;
+        if particle.age >= particle.lifetime {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        particle.velocity.y -= particle.gravity * dt;
+        transform.translation += particle.velocity * dt;
+        let alpha = 1.0 - particle.age / particle.lifetime;
+        sprite.color.set_alpha(alpha);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch_card::ScratchBackend;
+
+    #[test]
+    fn debris_shares_the_card_layers() {
+        let mut app = App::new();
+        app.init_resource::<Assets<Image>>()
+            .add_systems(Update, spawn_debris);
+
+        let mut debris = ScratchDebris {
+            particles_per_pixel: 1.0,
+            ..default()
+        };
+        debris.record(IVec2::new(4, 4), 2, 5);
+        let layers = RenderLayers::layer(3);
+        app.world_mut().spawn((
+            ScratchCard::new(Handle::default(), Handle::default()),
+            GlobalTransform::default(),
+            Aabb::from_min_max(Vec3::splat(-4.0), Vec3::splat(4.0)),
+            ScratchMask::new(UVec2::splat(8), Handle::default(), ScratchBackend::Cpu),
+            debris,
+            layers.clone(),
+        ));
+        app.update();
+
+        let mut particles = app
+            .world_mut()
+            .query_filtered::<Option<&RenderLayers>, With<ScratchDebrisParticle>>();
+        let particles = particles.iter(app.world()).collect::<Vec<_>>();
+        assert_eq!(particles.len(), 5);
+        assert!(particles.iter().all(|particle| *particle == Some(&layers)));
+    }
+}
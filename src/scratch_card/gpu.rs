@@ -0,0 +1,488 @@
+use std::{
+    borrow::Cow,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use bevy::{
+    platform::collections::HashSet,
+    prelude::*,
+    render::{
+        Extract, ExtractSchedule, Render, RenderApp, RenderSet,
+        graph::CameraDriverLabel,
+        render_asset::RenderAssets,
+        render_graph::{self, RenderGraph, RenderLabel},
+        render_resource::{
+            binding_types::{texture_storage_2d, uniform_buffer},
+            *,
+        },
+        renderer::{RenderAdapter, RenderContext, RenderDevice, RenderQueue},
+        texture::GpuImage,
+    },
+};
+
+use super::{
+    ScratchBackend, ScratchMask, ScratchStamp,
+    upload::{MaskWork, PendingMaskWork, extract_mask_uploads, queue_mask_uploads},
+};
+
+const SCRATCH_UPDATE_SHADER_PATH: &str = "shaders/scratch_update.wgsl";
+const WORKGROUP_SIZE: u32 = 8;
+
+/// Whether the render device can run the compute painter, inserted once rendering is initialised
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct ScratchGpuSupport(pub bool);
+
+/// Paints [`super::ScratchBackend::Gpu`] masks with `scratch_update.wgsl`
+pub(super) struct ScratchComputePlugin;
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+struct ScratchMaskComputeLabel;
+
+impl Plugin for ScratchComputePlugin {
+    fn build(&self, app: &mut App) {
+        let failed = ScratchComputeFailed::default();
+        app.init_resource::<ScratchGpuStamps>()
+            .insert_resource(failed.clone())
+            .add_systems(
+                PostUpdate,
+                (
+                    fall_back_to_cpu.before(queue_mask_uploads),
+                    queue_gpu_stamps,
+                )
+                    .chain(),
+            );
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .insert_resource(failed)
+            .init_resource::<ScratchStampBatches>()
+            .add_systems(
+                ExtractSchedule,
+                extract_gpu_stamps.after(extract_mask_uploads),
+            )
+            .add_systems(
+                Render,
+                (check_compute_pipeline, prepare_stamp_batches)
+                    .chain()
+                    .in_set(RenderSet::PrepareBindGroups),
+            );
+
+        let mut render_graph = render_app.world_mut().resource_mut::<RenderGraph>();
+        render_graph.add_node(ScratchMaskComputeLabel, ScratchMaskComputeNode);
+        render_graph.add_node_edge(ScratchMaskComputeLabel, CameraDriverLabel);
+    }
+
+    fn finish(&self, app: &mut App) {
+        let supported = match (
+            app.world().get_resource::<RenderAdapter>(),
+            app.world().get_resource::<RenderDevice>(),
+        ) {
+            (Some(adapter), Some(device)) => {
+                device
+                    .features()
+                    .contains(WgpuFeatures::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)
+                    && adapter
+                        .get_texture_format_features(TextureFormat::R8Unorm)
+                        .flags
+                        .contains(TextureFormatFeatureFlags::STORAGE_READ_WRITE)
+            }
+            _ => false,
+        };
+        app.insert_resource(ScratchGpuSupport(supported));
+
+        if !supported {
+            return;
+        }
+        if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.init_resource::<ScratchComputePipeline>();
+        }
+    }
+}
+
+use paint_data::PaintData;
+
+// `ShaderType` emits its layout checks next to the struct rather than inside it,
+// so the unused lint is allowed on a module holding just this uniform
+#[allow(dead_code)]
+mod paint_data {
+    use bevy::{prelude::*, render::render_resource::ShaderType};
+
+    /// Uniform matching `PaintData` in `scratch_update.wgsl`
+    #[derive(Clone, Copy, ShaderType)]
+    pub struct PaintData {
+        /// Brush centre in mask UV, the shader snaps it back to the pixel grid
+        pub position: Vec2,
+        pub radius: f32,
+        pub paint_value: f32,
+        /// First pixel of the dispatched area, added to the invocation id
+        pub origin: UVec2,
+    }
+}
+
+impl PaintData {
+    /// Uniform of a stamp and the workgroups covering its part of a `size` mask,
+    /// `None` if it misses the mask
+    fn dispatch(stamp: &ScratchStamp, size: UVec2) -> Option<(Self, UVec2)> {
+        let rect = stamp
+            .bounds()
+            .intersect(IRect::from_corners(IVec2::ZERO, size.as_ivec2()));
+        if rect.is_empty() {
+            return None;
+        }
+        let rect = rect.as_urect();
+        let paint = Self {
+            position: stamp.center.as_vec2() / size.as_vec2(),
+            radius: stamp.radius as f32,
+            paint_value: stamp.value as f32 / 255.0,
+            origin: rect.min,
+        };
+        let workgroups = (rect.size() + UVec2::splat(WORKGROUP_SIZE - 1)) / WORKGROUP_SIZE;
+        Some((paint, workgroups))
+    }
+}
+
+/// Set by the render world once the compute painter can't run, shared with the main world
+#[derive(Resource, Clone, Default)]
+struct ScratchComputeFailed(Arc<AtomicBool>);
+
+/// Stamps of one mask in the order they were scratched
+#[derive(Clone)]
+struct GpuStamps {
+    image: AssetId<Image>,
+    stamps: Vec<ScratchStamp>,
+}
+
+/// Stamps produced by the main world this frame
+#[derive(Resource, Default)]
+struct ScratchGpuStamps(Vec<GpuStamps>);
+
+/// One mask's dispatches for this frame, a uniform offset and workgroup count per stamp
+struct StampBatch {
+    bind_group: BindGroup,
+    dispatches: Vec<(u32, UVec2)>,
+}
+
+#[derive(Resource, Default)]
+struct ScratchStampBatches {
+    uniforms: DynamicUniformBuffer<PaintData>,
+    batches: Vec<StampBatch>,
+}
+
+#[derive(Resource)]
+struct ScratchComputePipeline {
+    layout: BindGroupLayout,
+    pipeline: CachedComputePipelineId,
+}
+
+impl FromWorld for ScratchComputePipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let layout = render_device.create_bind_group_layout(
+            "scratch_mask_compute_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::COMPUTE,
+                (
+                    texture_storage_2d(TextureFormat::R8Unorm, StorageTextureAccess::ReadWrite),
+                    uniform_buffer::<PaintData>(true),
+                ),
+            ),
+        );
+        let shader = world.load_asset(SCRATCH_UPDATE_SHADER_PATH);
+        let pipeline =
+            world
+                .resource::<PipelineCache>()
+                .queue_compute_pipeline(ComputePipelineDescriptor {
+                    label: Some("scratch_mask_compute_pipeline".into()),
+                    layout: vec![layout.clone()],
+                    push_constant_ranges: Vec::new(),
+                    shader,
+                    shader_defs: vec![],
+                    entry_point: Cow::from("update_mask"),
+                    zero_initialize_workgroup_memory: false,
+                });
+
+        Self { layout, pipeline }
+    }
+}
+
+/// Move every GPU mask to the CPU path after the compute painter failed, re-uploading it whole
+/// since the stamps it had queued were dropped
+fn fall_back_to_cpu(
+    failed: Res<ScratchComputeFailed>,
+    mut support: ResMut<ScratchGpuSupport>,
+    mut masks: Query<&mut ScratchMask>,
+) {
+    if !failed.0.load(Ordering::Relaxed) {
+        return;
+    }
+    support.0 = false;
+    for mut mask in &mut masks {
+        if mask.backend == ScratchBackend::Gpu {
+            mask.backend = ScratchBackend::Cpu;
+            mask.mark_all_dirty();
+        }
+    }
+}
+
+fn queue_gpu_stamps(mut queued: ResMut<ScratchGpuStamps>, mut masks: Query<&mut ScratchMask>) {
+    queued.0.clear();
+    for mut mask in &mut masks {
+        // Like uploads, handing stamps over isn't a change to the mask
+        let stamps = mask.bypass_change_detection().take_gpu_stamps();
+        if stamps.is_empty() {
+            continue;
+        }
+        queued.0.push(GpuStamps {
+            image: mask.image.id(),
+            stamps,
+        });
+    }
+}
+
+fn extract_gpu_stamps(
+    queued: Extract<Res<ScratchGpuStamps>>,
+    mut pending: ResMut<PendingMaskWork>,
+) {
+    pending.0.extend(
+        queued
+            .0
+            .iter()
+            .map(|queued| (queued.image, MaskWork::Stamps(queued.stamps.clone()))),
+    );
+}
+
+/// Drop queued stamps for good once the compute pipeline can't be built
+fn check_compute_pipeline(
+    pipeline: Option<Res<ScratchComputePipeline>>,
+    pipeline_cache: Res<PipelineCache>,
+    failed: Res<ScratchComputeFailed>,
+    mut pending: ResMut<PendingMaskWork>,
+) {
+    if !pending
+        .0
+        .iter()
+        .any(|(_, work)| matches!(work, MaskWork::Stamps(_)))
+    {
+        return;
+    }
+    let state = pipeline
+        .as_ref()
+        .map(|pipeline| pipeline_cache.get_compute_pipeline_state(pipeline.pipeline));
+    match state {
+        Some(
+            CachedPipelineState::Ok(_)
+            | CachedPipelineState::Queued
+            | CachedPipelineState::Creating(_)
+            | CachedPipelineState::Err(
+                PipelineCacheError::ShaderNotLoaded(_)
+                | PipelineCacheError::ShaderImportNotYetAvailable,
+            ),
+        ) => return,
+        Some(CachedPipelineState::Err(err)) if !failed.0.load(Ordering::Relaxed) => {
+            error!("Scratch mask compute shader failed, painting on the CPU instead: {err}");
+        }
+        None if !failed.0.load(Ordering::Relaxed) => {
+            error!("Scratch masks can't be painted on this GPU, painting on the CPU instead");
+        }
+        _ => {}
+    }
+    // The main world re-uploads the masks these stamps were painting
+    failed.0.store(true, Ordering::Relaxed);
+    pending
+        .0
+        .retain(|(_, work)| !matches!(work, MaskWork::Stamps(_)));
+}
+
+fn prepare_stamp_batches(
+    pipeline: Option<Res<ScratchComputePipeline>>,
+    pipeline_cache: Res<PipelineCache>,
+    gpu_images: Res<RenderAssets<GpuImage>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    mut pending: ResMut<PendingMaskWork>,
+    mut batches: ResMut<ScratchStampBatches>,
+) {
+    let batches = &mut *batches;
+    batches.uniforms.clear();
+    batches.batches.clear();
+
+    let Some(pipeline) = pipeline else {
+        return;
+    };
+    // Keep everything pending until the shader has compiled
+    if pipeline_cache
+        .get_compute_pipeline(pipeline.pipeline)
+        .is_none()
+    {
+        return;
+    }
+
+    // Uploads still pending here come after stamps, they must wait for the stamps' dispatch
+    let mut ready = Vec::new();
+    let mut blocked = HashSet::new();
+    pending.0.retain(|(image, work)| {
+        if blocked.contains(image) {
+            return true;
+        }
+        let (MaskWork::Stamps(stamps), Some(gpu_image)) = (work, gpu_images.get(*image)) else {
+            blocked.insert(*image);
+            return true;
+        };
+        let size = UVec2::new(gpu_image.size.width, gpu_image.size.height);
+        let dispatches = stamps
+            .iter()
+            .filter_map(|stamp| PaintData::dispatch(stamp, size))
+            .map(|(paint, workgroups)| (batches.uniforms.push(&paint), workgroups))
+            .collect::<Vec<_>>();
+        ready.push((*image, dispatches));
+        false
+    });
+    if ready.is_empty() {
+        return;
+    }
+
+    batches.uniforms.write_buffer(&render_device, &render_queue);
+    let Some(uniforms) = batches.uniforms.binding() else {
+        return;
+    };
+
+    for (image, dispatches) in ready {
+        let Some(gpu_image) = gpu_images.get(image) else {
+            continue;
+        };
+        let bind_group = render_device.create_bind_group(
+            "scratch_mask_compute_bind_group",
+            &pipeline.layout,
+            &BindGroupEntries::sequential((&gpu_image.texture_view, uniforms.clone())),
+        );
+        batches.batches.push(StampBatch {
+            bind_group,
+            dispatches,
+        });
+    }
+}
+
+struct ScratchMaskComputeNode;
+
+impl render_graph::Node for ScratchMaskComputeNode {
+    fn run(
+        &self,
+        _graph: &mut render_graph::RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), render_graph::NodeRunError> {
+        let batches = world.resource::<ScratchStampBatches>();
+        if batches.batches.is_empty() {
+            return Ok(());
+        }
+        let Some(pipeline) = world.get_resource::<ScratchComputePipeline>() else {
+            return Ok(());
+        };
+        let Some(compute_pipeline) = world
+            .resource::<PipelineCache>()
+            .get_compute_pipeline(pipeline.pipeline)
+        else {
+            return Ok(());
+        };
+
+        let mut pass =
+            render_context
+                .command_encoder()
+                .begin_compute_pass(&ComputePassDescriptor {
+                    label: Some("scratch_mask_compute_pass"),
+                    ..default()
+                });
+        pass.set_pipeline(compute_pipeline);
+
+        // One dispatch per stamp, over just its bounds, keeps the max blending in scratch order
+        for batch in &batches.batches {
+            for &(offset, workgroups) in &batch.dispatches {
+                pass.set_bind_group(0, &batch.bind_group, &[offset]);
+                pass.dispatch_workgroups(workgroups.x, workgroups.y, 1);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch_card::ScratchBackend;
+
+    /// A dispatch of `update_mask` in `scratch_update.wgsl`, run on the CPU over an r8unorm texture
+    fn shader_paint(data: &mut [u8], size: UVec2, paint: &PaintData, workgroups: UVec2) {
+        let paint_pos = (paint.position * size.as_vec2()).round().as_ivec2();
+        let invocations = workgroups * WORKGROUP_SIZE;
+        for global_y in 0..invocations.y {
+            for global_x in 0..invocations.x {
+                let UVec2 { x, y } = UVec2::new(global_x, global_y) + paint.origin;
+                if x >= size.x || y >= size.y {
+                    continue;
+                }
+                let offset = IVec2::new(x as i32, y as i32) - paint_pos;
+                if offset.length_squared() as f32 > paint.radius * paint.radius {
+                    continue;
+                }
+                let index = (y * size.x + x) as usize;
+                let current = data[index] as f32 / 255.0;
+                data[index] = (current.max(paint.paint_value) * 255.0).round() as u8;
+            }
+        }
+    }
+
+    #[test]
+    fn failed_painter_reuploads_gpu_masks() {
+        let mut app = App::new();
+        let failed = ScratchComputeFailed::default();
+        failed.0.store(true, Ordering::Relaxed);
+        app.insert_resource(failed)
+            .insert_resource(ScratchGpuSupport(true))
+            .add_systems(Update, fall_back_to_cpu);
+
+        let size = UVec2::new(8, 4);
+        let mut mask = ScratchMask::new(size, Handle::default(), ScratchBackend::Gpu);
+        mask.scratch(ScratchStamp::circle(IVec2::new(3, 2), 1, 255));
+        let card = app.world_mut().spawn(mask).id();
+        app.update();
+
+        assert!(!app.world().resource::<ScratchGpuSupport>().0);
+        let mut mask = app.world_mut().get_mut::<ScratchMask>(card).unwrap();
+        assert_eq!(mask.backend, ScratchBackend::Cpu);
+        assert!(mask.take_gpu_stamps().is_empty());
+        let (rect, bytes) = mask.take_dirty().unwrap();
+        assert_eq!(rect, URect::from_corners(UVec2::ZERO, size));
+        assert_eq!(bytes, mask.data);
+    }
+
+    #[test]
+    fn shader_matches_cpu_circles() {
+        let size = UVec2::new(37, 23);
+        let stamps = [
+            (IVec2::new(10, 10), 4, 255),
+            (IVec2::new(0, 0), 6, 128),
+            (IVec2::new(36, 22), 3, 200),
+            (IVec2::new(18, 11), 7, 90),
+            (IVec2::new(-2, 12), 5, 255),
+            (IVec2::new(20, 5), 0, 60),
+            (IVec2::new(-9, 30), 4, 255),
+        ];
+
+        let mut cpu = ScratchMask::new(size, Handle::default(), ScratchBackend::Cpu);
+        let mut gpu = vec![0u8; (size.x * size.y) as usize];
+        for (center, radius, value) in stamps {
+            let stamp = ScratchStamp::circle(center, radius, value);
+            cpu.paint(&stamp);
+            if let Some((paint, workgroups)) = PaintData::dispatch(&stamp, size) {
+                shader_paint(&mut gpu, size, &paint, workgroups);
+            }
+            assert_eq!(cpu.data, gpu, "after {center} radius {radius}");
+        }
+    }
+}
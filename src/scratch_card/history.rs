@@ -0,0 +1,92 @@
+use bevy::prelude::*;
+
+use super::{ScratchMask, ScratchStrokes};
+use crate::mask_history::{DEFAULT_HISTORY_LIMIT, MaskHistory};
+
+/// Insert on a card to record each finished stroke as an undo step, see [`ScratchMask::undo`]
+#[derive(Component, Debug, Clone)]
+pub struct ScratchHistory {
+    /// Undo steps kept before the oldest is dropped
+    pub limit: usize,
+}
+
+impl Default for ScratchHistory {
+    fn default() -> Self {
+        Self {
+            limit: DEFAULT_HISTORY_LIMIT,
+        }
+    }
+}
+
+impl ScratchMask {
+    pub fn can_undo(&self) -> bool {
+        self.history.as_ref().is_some_and(MaskHistory::can_undo)
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.history.as_ref().is_some_and(MaskHistory::can_redo)
+    }
+
+    /// Revert the card's last stroke, returns whether there was one
+    pub fn undo(&mut self) -> bool {
+        let Some(rect) = self
+            .history
+            .as_mut()
+            .and_then(|history| history.undo(&mut self.data))
+        else {
+            return false;
+        };
+        self.mark_dirty(rect);
+        true
+    }
+
+    /// Re-apply the card's last undone stroke, returns whether there was one
+    pub fn redo(&mut self) -> bool {
+        let Some(rect) = self
+            .history
+            .as_mut()
+            .and_then(|history| history.redo(&mut self.data))
+        else {
+            return false;
+        };
+        self.mark_dirty(rect);
+        true
+    }
+}
+
+/// A stroke ends by removing its interpolator, possibly without touching the mask
+type StrokeOrMaskChanged = Or<(
+    Changed<ScratchMask>,
+    Changed<ScratchStrokes>,
+    Changed<ScratchHistory>,
+)>;
+
+/// Start recording once a card has a [`ScratchHistory`], and commit once no pointer is mid-stroke
+pub(super) fn record_scratch_history(
+    mut cards: Query<(&mut ScratchMask, &ScratchStrokes, &ScratchHistory), StrokeOrMaskChanged>,
+) {
+    for (mut mask, strokes, settings) in &mut cards {
+        // Bookkeeping only, the mask's pixels don't change here
+        let mask = mask.bypass_change_detection();
+        let width = mask.size.x;
+        let history = mask
+            .history
+            .get_or_insert_with(|| MaskHistory::new(width, 1));
+        history.limit = settings.limit;
+        if strokes.0.is_empty() {
+            history.commit(&mask.data);
+        }
+    }
+}
+
+/// Stop recording on cards that lost their [`ScratchHistory`]
+pub(super) fn drop_scratch_history(
+    mut removed: RemovedComponents<ScratchHistory>,
+    mut masks: Query<&mut ScratchMask>,
+) {
+    for entity in removed.read() {
+        if let Ok(mut mask) = masks.get_mut(entity) {
+            mask.bypass_change_detection().history = None;
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use std::sync::Arc;
+
+use bevy::{
+    ecs::system::SystemParam,
+    input::touch::{ForceTouch, Touch},
+    math::Ray3d,
+    platform::collections::HashMap,
+    prelude::*,
+    render::{camera::RenderTarget, primitives::Aabb, view::RenderLayers},
+    window::{PrimaryWindow, WindowRef},
+};
+
+use super::{
+    BrushFootprint, ScratchBrush, ScratchCard, ScratchDebris, ScratchMask, ScratchPointer,
+    ScratchStamp, ScratchStrokeLog, ScratchStrokes,
+};
+
+/// Mouse buttons carry no pressure, stamps use the brush opacity as is
+const MOUSE_PRESSURE: f32 = 1.0;
+
+/// Footprints of custom brush images, resampled once per image and radius
+#[derive(Default)]
+pub(super) struct FootprintCache(HashMap<(AssetId<Image>, i32), Arc<BrushFootprint>>);
+
+impl FootprintCache {
+    /// Footprint for the brush, `None` for circular brushes or while the image is loading
+    pub(super) fn get(
+        &mut self,
+        images: &Assets<Image>,
+        brush: &ScratchBrush,
+        mask_scale: f32,
+    ) -> Option<Arc<BrushFootprint>> {
+        let handle = brush.stamp_image.as_ref()?;
+        let radius = brush.radius_pixels(mask_scale);
+        let key = (handle.id(), radius);
+        if let Some(footprint) = self.0.get(&key) {
+            return Some(footprint.clone());
+        }
+        let footprint = Arc::new(BrushFootprint::from_image(images.get(handle)?, radius)?);
+        self.0.insert(key, footprint.clone());
+        Some(footprint)
+    }
+}
+
+/// A pressed pointer this frame, in primary window coordinates
+struct PointerSample {
+    pointer: ScratchPointer,
+    position: Vec2,
+    pressure: f32,
+}
+
+/// Mouse and touch state, read as one set of pointer samples
+#[derive(SystemParam)]
+pub(super) struct Pointers<'w, 's> {
+    windows: Query<'w, 's, (Entity, &'static Window), With<PrimaryWindow>>,
+    buttons: Res<'w, ButtonInput<MouseButton>>,
+    touches: Res<'w, Touches>,
+}
+
+impl Pointers<'_, '_> {
+    /// Every pointer currently pressed, the mouse first
+    fn samples(&self) -> Vec<PointerSample> {
+        let mut samples = Vec::new();
+        if self.buttons.pressed(MouseButton::Left)
+            && let Some(position) = self
+                .windows
+                .single()
+                .ok()
+                .and_then(|(_, window)| window.cursor_position())
+        {
+            samples.push(PointerSample {
+                pointer: ScratchPointer::Mouse,
+                position,
+                pressure: MOUSE_PRESSURE,
+            });
+        }
+        samples.extend(self.touches.iter().map(|touch| PointerSample {
+            pointer: ScratchPointer::Touch(touch.id()),
+            position: touch.position(),
+            pressure: touch_pressure(touch),
+        }));
+        samples
+    }
+
+    fn primary_window(&self) -> Option<Entity> {
+        self.windows.single().ok().map(|(entity, _)| entity)
+    }
+}
+
+/// Card and camera a pointer's drag started on
+#[derive(Clone, Copy)]
+pub(super) struct ActiveStroke {
+    card: Entity,
+    camera: Entity,
+    pressure: f32,
+}
+
+type CardData = (
+    Entity,
+    &'static ScratchCard,
+    &'static GlobalTransform,
+    &'static Aabb,
+    Option<&'static RenderLayers>,
+    &'static mut ScratchMask,
+    &'static mut ScratchStrokes,
+    Option<&'static mut ScratchStrokeLog>,
+    Option<&'static mut ScratchDebris>,
+);
+
+/// Handle mouse and touch input, each pointer scratches the card its drag started on
+pub(super) fn handle_pointer_input(
+    pointers: Pointers,
+    time: Res<Time>,
+    camera_q: Query<(Entity, &Camera, &GlobalTransform, Option<&RenderLayers>)>,
+    images: Res<Assets<Image>>,
+    mut footprints: Local<FootprintCache>,
+    mut active: Local<HashMap<ScratchPointer, ActiveStroke>>,
+    mut card_q: Query<CardData>,
+) {
+    let samples = pointers.samples();
+    let now = time.elapsed_secs_f64();
+
+    // Lifting a pointer finishes its stroke
+    active.retain(|pointer, stroke| {
+        if samples.iter().any(|sample| sample.pointer == *pointer) {
+            return true;
+        }
+        if let Ok((_, card, .., mut mask, mut strokes, mut log, mut debris)) =
+            card_q.get_mut(stroke.card)
+            && let Some(mut interpolator) = strokes.0.remove(pointer)
+        {
+            let brush = &card.brush;
+            let footprint = footprints.get(&images, brush, card.mask_scale);
+            for centre in interpolator.end(brush.spacing_pixels(card.mask_scale), brush.smoothing) {
+                let stamp =
+                    brush.stamp(centre, stroke.pressure, card.mask_scale, footprint.clone());
+                apply_stamp(
+                    &mut mask,
+                    log.as_deref_mut(),
+                    debris.as_deref_mut(),
+                    now,
+                    stamp,
+                );
+            }
+        }
+        false
+    });
+
+    // Cameras drawn last are on top, so they get the first chance at a pointer
+    let primary_window = pointers.primary_window();
+    let mut cameras = camera_q
+        .iter()
+        .filter(|(_, camera, ..)| {
+            camera.is_active && renders_to_primary(&camera.target, primary_window)
+        })
+        .collect::<Vec<_>>();
+    cameras.sort_by_key(|(_, camera, ..)| std::cmp::Reverse(camera.order));
+
+    for sample in samples {
+        // A drag starts on the closest card under the pointer and stays with it
+        // even when the pointer leaves the card, so fast swipes reach the edges
+        if !active.contains_key(&sample.pointer) {
+            let hit = cameras
+                .iter()
+                .find_map(|(camera_entity, camera, cam_tf, cam_layers)| {
+                    let viewport = camera.logical_viewport_rect()?;
+                    if !viewport.contains(sample.position) {
+                        return None;
+                    }
+                    let ray = camera.viewport_to_world(cam_tf, sample.position).ok()?;
+                    let cam_layers = cam_layers.cloned().unwrap_or_default();
+                    card_q
+                        .iter()
+                        .filter(|(_, _, _, _, layers, ..)| {
+                            cam_layers.intersects(&layers.cloned().unwrap_or_default())
+                        })
+                        .filter_map(|(entity, _, card_tf, aabb, ..)| {
+                            let (uv, distance) = card_uv(&ray, card_tf, aabb)?;
+                            let on_card = uv.cmpge(Vec2::ZERO).all() && uv.cmple(Vec2::ONE).all();
+                            on_card.then_some((entity, distance))
+                        })
+                        .min_by(|a, b| a.1.total_cmp(&b.1))
+                        .map(|(card, _)| ActiveStroke {
+                            card,
+                            camera: *camera_entity,
+                            pressure: sample.pressure,
+                        })
+                });
+            let Some(hit) = hit else {
+                continue;
+            };
+            active.insert(sample.pointer, hit);
+        }
+
+        let Some(stroke) = active.get_mut(&sample.pointer) else {
+            continue;
+        };
+        stroke.pressure = sample.pressure;
+        let stroke = *stroke;
+
+        let Some(ray) = camera_q
+            .get(stroke.camera)
+            .ok()
+            .and_then(|(_, camera, cam_tf, _)| {
+                camera.viewport_to_world(cam_tf, sample.position).ok()
+            })
+        else {
+            continue;
+        };
+        let Ok((_, card, card_tf, aabb, _, mut mask, mut strokes, mut log, mut debris)) =
+            card_q.get_mut(stroke.card)
+        else {
+            active.remove(&sample.pointer);
+            continue;
+        };
+        let Some((uv, _)) = card_uv(&ray, card_tf, aabb) else {
+            continue;
+        };
+        let pixel = uv * mask.size.as_vec2();
+
+        // The mask image and material are reused, only the stamps themselves reach the GPU
+        let brush = &card.brush;
+        let footprint = footprints.get(&images, brush, card.mask_scale);
+        let interpolator = strokes.0.entry(sample.pointer).or_default();
+        for centre in interpolator.push(
+            pixel,
+            brush.spacing_pixels(card.mask_scale),
+            brush.smoothing,
+        ) {
+            let stamp = brush.stamp(centre, sample.pressure, card.mask_scale, footprint.clone());
+            apply_stamp(
+                &mut mask,
+                log.as_deref_mut(),
+                debris.as_deref_mut(),
+                now,
+                stamp,
+            );
+        }
+    }
+}
+
+/// Scratch a stamp into the mask, recording it for the card's stroke log and debris if any
+fn apply_stamp(
+    mask: &mut ScratchMask,
+    log: Option<&mut ScratchStrokeLog>,
+    debris: Option<&mut ScratchDebris>,
+    time: f64,
+    stamp: ScratchStamp,
+) {
+    if let Some(log) = log {
+        log.record(time, stamp.clone());
+    }
+    let revealed = mask.revealed();
+    let (center, radius) = (stamp.center, stamp.radius);
+    mask.scratch(stamp);
+    if let Some(debris) = debris {
+        debris.record(center, radius, mask.revealed() - revealed);
+    }
+}
+
+/// Touch pressure in `0..=1`, full strength when the device doesn't report force
+fn touch_pressure(touch: &Touch) -> f32 {
+    match touch.force() {
+        // 1.0 is an average touch, anything firmer scratches at full strength
+        Some(ForceTouch::Calibrated { force, .. }) => force.min(1.0) as f32,
+        Some(ForceTouch::Normalized(force)) => force as f32,
+        None => 1.0,
+    }
+}
+
+/// Whether a camera draws into the primary window, where pointer positions come from
+fn renders_to_primary(target: &RenderTarget, primary_window: Option<Entity>) -> bool {
+    match target {
+        RenderTarget::Window(WindowRef::Primary) => true,
+        RenderTarget::Window(WindowRef::Entity(entity)) => Some(*entity) == primary_window,
+        _ => false,
+    }
+}
+
+/// Where a ray crosses the card's plane, as mesh UV (y down, outside `0..=1` when off
+/// the card) and distance along the ray; `None` if it misses the plane
+fn card_uv(ray: &Ray3d, card_tf: &GlobalTransform, aabb: &Aabb) -> Option<(Vec2, f32)> {
+    // Intersect in the card's local space, which undoes translation, rotation and scale
+    let world_from_local = card_tf.affine();
+    let local_from_world = world_from_local.inverse();
+    let origin = local_from_world.transform_point3(ray.origin);
+    let direction = local_from_world.transform_vector3(*ray.direction);
+    if direction.z.abs() <= f32::EPSILON {
+        return None;
+    }
+    let t = -origin.z / direction.z;
+    if t < 0.0 {
+        return None;
+    }
+    let local = (origin + direction * t).truncate();
+
+    let min = aabb.min().truncate();
+    let max = aabb.max().truncate();
+    let uv = Vec2::new(local.x - min.x, max.y - local.y) / (max - min);
+    let distance = world_from_local
+        .transform_point3(local.extend(0.0))
+        .distance(ray.origin);
+    Some((uv, distance))
+}
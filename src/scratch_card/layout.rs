@@ -0,0 +1,270 @@
+use bevy::prelude::*;
+
+use super::{ScratchCardMaterial, ScratchMask, region::ScratchRegion, splitmix64};
+
+const DEFAULT_SLOT_THRESHOLD: f32 = 0.6;
+const DEFAULT_MATCH_COUNT: usize = 3;
+
+/// A symbol from the prize table
+#[derive(Debug, Clone)]
+pub struct PrizeSymbol {
+    pub name: String,
+    pub image: Handle<Image>,
+    /// Relative chance of being drawn for a slot by a seeded outcome
+    pub weight: f32,
+}
+
+impl PrizeSymbol {
+    pub fn new(name: impl Into<String>, image: Handle<Image>) -> Self {
+        Self {
+            name: name.into(),
+            image,
+            weight: 1.0,
+        }
+    }
+}
+
+/// Which symbol ends up in each slot
+#[derive(Debug, Clone)]
+pub enum PrizeOutcome {
+    /// Draw each slot from the symbol weights, the same seed always gives the same card
+    Seeded(u64),
+    /// Symbol indices decided elsewhere, e.g. by a server, one per slot
+    Fixed(Vec<usize>),
+}
+
+impl PrizeOutcome {
+    /// Symbol index for every slot, `None` if a fixed outcome doesn't fit the table
+    pub fn resolve(&self, symbols: &[PrizeSymbol], slot_count: usize) -> Option<Vec<usize>> {
+        match self {
+            Self::Fixed(indices) => (indices.len() == slot_count
+                && indices.iter().all(|&index| index < symbols.len()))
+            .then(|| indices.clone()),
+            Self::Seeded(seed) => {
+                let total: f32 = symbols.iter().map(|symbol| symbol.weight.max(0.0)).sum();
+                if total <= 0.0 {
+                    return None;
+                }
+                let mut state = *seed;
+                let outcome = (0..slot_count)
+                    .map(|_| {
+                        // Top 24 bits give an exact f32 in `0..1`
+                        let roll = (splitmix64(&mut state) >> 40) as f32 / (1 << 24) as f32 * total;
+                        let mut acc = 0.0;
+                        symbols
+                            .iter()
+                            .position(|symbol| {
+                                acc += symbol.weight.max(0.0);
+                                roll < acc
+                            })
+                            .unwrap_or(symbols.len() - 1)
+                    })
+                    .collect();
+                Some(outcome)
+            }
+        }
+    }
+}
+
+/// Hidden symbol slots composited over a card's reveal texture.
+///
+/// Spawn it with a [`super::ScratchCard`]; once the images are loaded the outcome is
+/// drawn onto the reveal layer and a [`ScratchPrizes`] is inserted to track the slots.
+/// A layout that can't be drawn, e.g. with compressed symbol images, is logged and removed
+/// so the card never reports prizes it doesn't show.
+#[derive(Component, Debug, Clone)]
+pub struct ScratchCardLayout {
+    pub symbols: Vec<PrizeSymbol>,
+    /// Slot areas in card UV (`0..=1`, y down)
+    pub slots: Vec<Rect>,
+    pub outcome: PrizeOutcome,
+    /// Fraction of a slot that must be scratched before its symbol counts as uncovered
+    pub slot_threshold: f32,
+    /// Uncovered slots showing the same symbol needed for [`PrizeMatched`]
+    pub match_count: usize,
+}
+
+impl ScratchCardLayout {
+    pub fn new(symbols: Vec<PrizeSymbol>, slots: Vec<Rect>, outcome: PrizeOutcome) -> Self {
+        Self {
+            symbols,
+            slots,
+            outcome,
+            slot_threshold: DEFAULT_SLOT_THRESHOLD,
+            match_count: DEFAULT_MATCH_COUNT,
+        }
+    }
+}
+
+/// Resolved outcome of a [`ScratchCardLayout`] and which slots are uncovered
+#[derive(Component, Debug, Clone)]
+pub struct ScratchPrizes {
+    /// Symbol index for each slot
+    pub symbols: Vec<usize>,
+    /// Slots tracked like [`super::ScratchRegions`], reached once uncovered
+    slots: Vec<ScratchRegion>,
+}
+
+impl ScratchPrizes {
+    pub fn is_uncovered(&self, slot: usize) -> bool {
+        self.slots.get(slot).is_some_and(ScratchRegion::is_reached)
+    }
+
+    /// Uncovered slots showing a symbol
+    pub fn uncovered_count(&self, symbol: usize) -> usize {
+        self.symbols
+            .iter()
+            .zip(&self.slots)
+            .filter(|&(&slot_symbol, slot)| slot.is_reached() && slot_symbol == symbol)
+            .count()
+    }
+}
+
+/// A slot passed the layout's [`ScratchCardLayout::slot_threshold`]
+#[derive(Event, Debug, Clone, Copy)]
+pub struct PrizeSlotUncovered {
+    pub card: Entity,
+    pub slot: usize,
+    pub symbol: usize,
+}
+
+/// [`ScratchCardLayout::match_count`] slots with the same symbol are uncovered
+#[derive(Event, Debug, Clone, Copy)]
+pub struct PrizeMatched {
+    pub card: Entity,
+    pub symbol: usize,
+}
+
+/// Pixels (max exclusive) of a `size` image covered by a UV rect, clipped to the image
+pub(super) fn uv_rect_to_pixels(rect: Rect, size: UVec2) -> URect {
+    let size = size.as_vec2();
+    let min = (rect.min * size).floor().max(Vec2::ZERO);
+    let max = (rect.max * size).ceil().min(size);
+    URect::from_corners(min.as_uvec2(), max.max(min).as_uvec2())
+}
+
+/// Draw each layout's outcome onto a copy of the reveal texture once its images are loaded
+pub(super) fn composite_prize_layouts(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<ScratchCardMaterial>>,
+    cards: Query<
+        (
+            Entity,
+            &ScratchCardLayout,
+            &MeshMaterial2d<ScratchCardMaterial>,
+        ),
+        Without<ScratchPrizes>,
+    >,
+) {
+    for (entity, layout, material) in &cards {
+        let Some(material) = materials.get_mut(&material.0) else {
+            continue;
+        };
+        let loaded = images.contains(&material.reveal_texture)
+            && layout
+                .symbols
+                .iter()
+                .all(|symbol| images.contains(&symbol.image));
+        if !loaded {
+            continue;
+        }
+
+        let Some(symbols) = layout.outcome.resolve(&layout.symbols, layout.slots.len()) else {
+            warn!("Scratch card {entity} has an outcome that doesn't match its layout");
+            commands.entity(entity).remove::<ScratchCardLayout>();
+            continue;
+        };
+
+        // Without the drawn symbols the card must not report prizes the player can't see
+        let Some(image) = composite(&images, &material.reveal_texture, layout, &symbols) else {
+            error!(
+                "Could not draw the prize layout of scratch card {entity}, its prizes are disabled"
+            );
+            commands.entity(entity).remove::<ScratchCardLayout>();
+            continue;
+        };
+        material.reveal_texture = images.add(image);
+        let slots = layout
+            .slots
+            .iter()
+            .enumerate()
+            .map(|(slot, rect)| {
+                ScratchRegion::rect(format!("slot {slot}"), *rect, layout.slot_threshold)
+            })
+            .collect();
+        commands
+            .entity(entity)
+            .insert(ScratchPrizes { symbols, slots });
+    }
+}
+
+/// Reveal texture with each slot's symbol alpha blended on top, `None` if a format isn't readable
+fn composite(
+    images: &Assets<Image>,
+    reveal: &Handle<Image>,
+    layout: &ScratchCardLayout,
+    symbols: &[usize],
+) -> Option<Image> {
+    let mut image = images.get(reveal)?.clone();
+    let size = image.size();
+
+    for (rect, &symbol) in layout.slots.iter().zip(symbols) {
+        let symbol_image = images.get(&layout.symbols[symbol].image)?;
+        let symbol_size = symbol_image.size();
+        let pixels = uv_rect_to_pixels(*rect, size);
+        let area = pixels.size().max(UVec2::ONE).as_vec2();
+
+        for y in pixels.min.y..pixels.max.y {
+            for x in pixels.min.x..pixels.max.x {
+                // Nearest sample, stretching the symbol over the slot
+                let local = (UVec2::new(x, y) - pixels.min).as_vec2() + 0.5;
+                let source = (local / area * symbol_size.as_vec2())
+                    .as_uvec2()
+                    .min(symbol_size - 1);
+                let top = symbol_image
+                    .get_color_at(source.x, source.y)
+                    .ok()?
+                    .to_linear();
+                let base = image.get_color_at(x, y).ok()?.to_linear();
+                let blended = base.mix(&top.with_alpha(base.alpha), top.alpha);
+                image.set_color_at(x, y, blended.into()).ok()?;
+            }
+        }
+    }
+
+    Some(image)
+}
+
+/// Mark slots uncovered as the mask changes and report matching symbols
+pub(super) fn update_prize_slots(
+    mut cards: Query<
+        (Entity, &ScratchCardLayout, &ScratchMask, &mut ScratchPrizes),
+        Changed<ScratchMask>,
+    >,
+    mut uncovered_events: EventWriter<PrizeSlotUncovered>,
+    mut matched: EventWriter<PrizeMatched>,
+) {
+    for (entity, layout, mask, mut prizes) in &mut cards {
+        for slot in 0..prizes.slots.len() {
+            let region = &mut prizes.slots[slot];
+            region.threshold = layout.slot_threshold;
+            if !region.reach(mask) {
+                continue;
+            }
+
+            let symbol = prizes.symbols[slot];
+            uncovered_events.write(PrizeSlotUncovered {
+                card: entity,
+                slot,
+                symbol,
+            });
+            if prizes.uncovered_count(symbol) == layout.match_count {
+                matched.write(PrizeMatched {
+                    card: entity,
+                    symbol,
+                });
+            }
+        }
+    }
+}
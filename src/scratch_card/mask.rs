@@ -0,0 +1,317 @@
+use bevy::{
+    asset::RenderAssetUsages,
+    image::ImageSampler,
+    math::{IRect, URect},
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages},
+};
+
+use std::sync::Arc;
+
+use super::{BrushFootprint, ScratchBackend, ScratchBlend};
+use crate::mask_history::MaskHistory;
+
+/// Mask value from which a pixel counts as revealed
+pub const REVEALED_VALUE: u8 = 128;
+/// Largest stamp radius in mask pixels accepted from untrusted data, e.g. a stroke log
+pub const MAX_STAMP_RADIUS: i32 = 4096;
+/// Largest distance of a stamp centre from the mask origin accepted from untrusted data
+pub const MAX_STAMP_OFFSET: i32 = 1 << 20;
+
+/// One brush dab in mask pixel space, shared by the CPU and GPU painters
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScratchStamp {
+    /// Centre pixel, may lie outside the mask
+    pub center: IVec2,
+    /// Radius in mask pixels
+    pub radius: i32,
+    /// Value written where the brush has full weight
+    pub value: u8,
+    /// Fraction of the radius at full weight, `1.0` for a hard edge
+    pub hardness: f32,
+    pub blend: ScratchBlend,
+    /// Custom footprint replacing the circle
+    pub footprint: Option<Arc<BrushFootprint>>,
+}
+
+impl ScratchStamp {
+    /// Hard circle blended with `max`
+    pub fn circle(center: IVec2, radius: i32, value: u8) -> Self {
+        Self {
+            center,
+            radius,
+            value,
+            hardness: 1.0,
+            blend: ScratchBlend::Max,
+            footprint: None,
+        }
+    }
+
+    /// Whether `scratch_update.wgsl` can paint this stamp, i.e. a hard circle blended with `max`
+    pub fn is_hard_circle(&self) -> bool {
+        self.hardness >= 1.0 && self.blend == ScratchBlend::Max && self.footprint.is_none()
+    }
+
+    /// Weight in `0..=1` at an offset from the centre
+    pub fn weight(&self, dx: i32, dy: i32) -> f32 {
+        // Widened so stamps built with huge radii can't overflow
+        let (dx, dy, radius) = (dx as i64, dy as i64, self.radius as i64);
+        let distance_sq = dx * dx + dy * dy;
+        if distance_sq > radius * radius {
+            return 0.0;
+        }
+        let (dx, dy) = (dx as i32, dy as i32);
+        let shape = match &self.footprint {
+            Some(footprint) => footprint.weight(dx, dy),
+            None => 1.0,
+        };
+
+        // Soft edge: full weight inside the hard core, smooth falloff to the rim
+        let core = self.radius as f32 * self.hardness.clamp(0.0, 1.0);
+        let distance = (distance_sq as f32).sqrt();
+        let falloff = if distance <= core {
+            1.0
+        } else {
+            let t = (distance - core) / (self.radius as f32 - core);
+            1.0 - t * t * (3.0 - 2.0 * t)
+        };
+
+        shape * falloff
+    }
+
+    /// Pixels the stamp may touch (max exclusive), not clamped to the mask
+    pub fn bounds(&self) -> IRect {
+        let radius = self.radius.max(0);
+        IRect::new(
+            self.center.x.saturating_sub(radius),
+            self.center.y.saturating_sub(radius),
+            self.center.x.saturating_add(radius).saturating_add(1),
+            self.center.y.saturating_add(radius).saturating_add(1),
+        )
+    }
+}
+
+/// Per-card mask state: 0 shows the cover layer, 255 shows the reveal texture.
+///
+/// `data` is the CPU copy of `image`; to edit it directly call [`ScratchMask::before_edit`]
+/// first and [`ScratchMask::mark_dirty`] after, so the edit is undoable and re-uploaded.
+#[derive(Component, Debug, Clone)]
+pub struct ScratchMask {
+    pub data: Vec<u8>,
+    /// Width and height in mask pixels
+    pub size: UVec2,
+    pub image: Handle<Image>,
+    pub backend: ScratchBackend,
+    revealed: u32,
+    dirty: Option<URect>,
+    gpu_stamps: Vec<ScratchStamp>,
+    /// Undo steps, kept while the card has a [`super::ScratchHistory`]
+    pub(super) history: Option<MaskHistory>,
+}
+
+impl ScratchMask {
+    /// Create a fully covered mask backed by `image`
+    pub fn new(size: UVec2, image: Handle<Image>, backend: ScratchBackend) -> Self {
+        Self {
+            data: vec![0u8; size.element_product() as usize],
+            size,
+            image,
+            backend,
+            revealed: 0,
+            dirty: None,
+            gpu_stamps: Vec::new(),
+            history: None,
+        }
+    }
+
+    /// Scratch the mask with a stamp, returns whether any pixel changed.
+    ///
+    /// The CPU copy is always updated; the texture is either re-uploaded from
+    /// it or painted by the compute shader depending on [`ScratchMask::backend`].
+    /// Stamps the shader can't reproduce are always uploaded.
+    pub fn scratch(&mut self, stamp: ScratchStamp) -> bool {
+        let Some(rect) = self.paint(&stamp) else {
+            return false;
+        };
+        match self.backend {
+            ScratchBackend::Gpu if stamp.is_hard_circle() => self.gpu_stamps.push(stamp),
+            _ => self.queue_upload(rect),
+        }
+        true
+    }
+
+    /// Apply a stamp to `data` only, returns the rectangle of changed pixels
+    pub fn paint(&mut self, stamp: &ScratchStamp) -> Option<URect> {
+        let size = self.size.as_ivec2();
+        let mut changed: Option<URect> = None;
+
+        // Only the part of the stamp on the mask, however large the stamp is
+        let bounds = stamp
+            .bounds()
+            .intersect(IRect::from_corners(IVec2::ZERO, size));
+        if bounds.is_empty() {
+            return None;
+        }
+        self.before_edit(bounds.as_urect());
+        for y in bounds.min.y..bounds.max.y {
+            for x in bounds.min.x..bounds.max.x {
+                let (Some(dx), Some(dy)) =
+                    (x.checked_sub(stamp.center.x), y.checked_sub(stamp.center.y))
+                else {
+                    continue;
+                };
+                let weight = stamp.weight(dx, dy);
+                if weight <= 0.0 {
+                    continue;
+                }
+                let amount = (stamp.value as f32 * weight).round() as u8;
+                let idx = (y * size.x + x) as usize;
+                let old = self.data[idx];
+                let new = match stamp.blend {
+                    ScratchBlend::Max => old.max(amount),
+                    ScratchBlend::Additive => old.saturating_add(amount),
+                };
+                if new != old {
+                    self.data[idx] = new;
+                    if old < REVEALED_VALUE && new >= REVEALED_VALUE {
+                        self.revealed += 1;
+                    }
+                    let pixel = URect::new(x as u32, y as u32, x as u32 + 1, y as u32 + 1);
+                    changed = Some(changed.map_or(pixel, |rect| rect.union(pixel)));
+                }
+            }
+        }
+
+        changed
+    }
+
+    /// Number of pixels at or above [`REVEALED_VALUE`]
+    pub fn revealed(&self) -> u32 {
+        self.revealed
+    }
+
+    /// Fraction of the mask that is revealed, in `0..=1`
+    pub fn revealed_fraction(&self) -> f32 {
+        self.revealed as f32 / self.data.len().max(1) as f32
+    }
+
+    /// Number of revealed pixels inside a rectangle (max exclusive), clipped to the mask
+    pub fn revealed_in(&self, rect: URect) -> u32 {
+        let rect = rect.intersect(URect::from_corners(UVec2::ZERO, self.size));
+        (rect.min.y..rect.max.y)
+            .map(|y| {
+                let row = (y * self.size.x) as usize;
+                count_revealed(&self.data[row + rect.min.x as usize..row + rect.max.x as usize])
+            })
+            .sum()
+    }
+
+    /// Uncover the whole card at once
+    pub fn reveal_all(&mut self) {
+        self.before_edit(URect::from_corners(UVec2::ZERO, self.size));
+        self.data.fill(255);
+        self.mark_all_dirty();
+    }
+
+    /// Call before editing a rectangle (max exclusive) of `data` directly, so the edit can be undone
+    pub fn before_edit(&mut self, rect: URect) {
+        if let Some(history) = &mut self.history {
+            history.before_edit(&self.data, rect);
+        }
+    }
+
+    /// Flag a rectangle (max exclusive) of `data` as edited outside of
+    /// [`ScratchMask::paint`], recounting revealed pixels and re-uploading it
+    pub fn mark_dirty(&mut self, rect: URect) {
+        self.revealed = count_revealed(&self.data);
+        self.queue_upload(rect);
+    }
+
+    /// Flag the whole mask as edited, see [`ScratchMask::mark_dirty`]
+    pub fn mark_all_dirty(&mut self) {
+        self.mark_dirty(URect::from_corners(UVec2::ZERO, self.size));
+    }
+
+    /// Schedule a rectangle (max exclusive) of `data` for upload to the GPU
+    fn queue_upload(&mut self, rect: URect) {
+        // `data` already holds the result of queued GPU stamps, uploading their area supersedes them
+        let rect = self
+            .gpu_stamps
+            .drain(..)
+            .fold(rect.as_irect(), |rect, stamp| rect.union(stamp.bounds()))
+            .intersect(IRect::from_corners(IVec2::ZERO, self.size.as_ivec2()));
+        if rect.is_empty() {
+            return;
+        }
+        let rect = rect.as_urect();
+        self.dirty = Some(self.dirty.map_or(rect, |dirty| dirty.union(rect)));
+    }
+
+    /// Take the pending dirty rectangle together with a tightly packed copy of its rows
+    pub(super) fn take_dirty(&mut self) -> Option<(URect, Vec<u8>)> {
+        let rect = self.dirty.take()?;
+        let mut bytes = Vec::with_capacity((rect.width() * rect.height()) as usize);
+        for y in rect.min.y..rect.max.y {
+            let row = (y * self.size.x) as usize;
+            bytes.extend_from_slice(
+                &self.data[row + rect.min.x as usize..row + rect.max.x as usize],
+            );
+        }
+        Some((rect, bytes))
+    }
+
+    /// Take the stamps waiting for the compute shader
+    pub(super) fn take_gpu_stamps(&mut self) -> Vec<ScratchStamp> {
+        std::mem::take(&mut self.gpu_stamps)
+    }
+}
+
+/// Create mask image.
+///
+/// The image only lives in the render world; later edits are written straight
+/// into its texture from [`ScratchMask`] dirty rectangles.
+pub fn create_mask_image(data: &[u8], size: UVec2) -> Image {
+    let mut img = Image::new(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data.to_vec(),
+        TextureFormat::R8Unorm,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+
+    img.texture_descriptor.usage = TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST;
+    img.sampler = ImageSampler::nearest();
+    img
+}
+
+/// Number of values at or above [`REVEALED_VALUE`]
+pub(super) fn count_revealed(data: &[u8]) -> u32 {
+    data.iter()
+        .filter(|&&value| value >= REVEALED_VALUE)
+        .count() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn huge_stamps_only_paint_the_mask() {
+        let mut mask = ScratchMask::new(UVec2::new(8, 4), Handle::default(), ScratchBackend::Cpu);
+        let rect = mask.paint(&ScratchStamp::circle(IVec2::ZERO, i32::MAX, 255));
+        assert_eq!(rect, Some(URect::new(0, 0, 8, 4)));
+        assert_eq!(mask.revealed(), 32);
+        assert_eq!(
+            mask.paint(&ScratchStamp::circle(
+                IVec2::splat(i32::MIN + 5),
+                i32::MAX,
+                255
+            )),
+            None
+        );
+    }
+}
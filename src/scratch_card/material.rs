@@ -0,0 +1,260 @@
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssets,
+        render_resource::{AsBindGroup, AsBindGroupShaderType, ShaderRef},
+        texture::GpuImage,
+    },
+    sprite::{AlphaMode2d, Material2d},
+};
+
+use scratch_cover_uniform::ScratchCoverUniform;
+use scratch_edge_uniform::ScratchEdgeUniform;
+pub use scratch_reveal_uniform::ScratchRevealUniform;
+
+const SCRATCH_CARD_SHADER_PATH: &str = "shaders/scratch_card.wgsl";
+const DEFAULT_COVER_TILING: f32 = 3.0;
+const DEFAULT_FOIL_SPEED: f32 = 0.6;
+const DEFAULT_FOIL_MAP_SCALE: f32 = 4.0;
+const DEFAULT_FOIL_SHARPNESS: f32 = 24.0;
+const DEFAULT_EDGE_WIDTH: f32 = 2.0;
+
+/// What scratched areas show
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScratchRevealAlpha {
+    /// The reveal texture
+    #[default]
+    Texture,
+    /// Whatever is behind the card, the reveal texture is ignored
+    Transparent,
+}
+
+/// Look of the cover layer
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScratchCover {
+    /// Times the cover texture repeats across the card
+    pub tiling: Vec2,
+    /// Shift of the repeated cover in texture repeats
+    pub offset: Vec2,
+    /// Multiplied with the cover texture
+    pub tint: Color,
+    /// Strength of the metallic foil highlight, `0` for a flat cover
+    pub shimmer: f32,
+    pub foil: ScratchFoil,
+    pub reveal_alpha: ScratchRevealAlpha,
+}
+
+/// Animated foil highlight on the cover, scaled by [`ScratchCover::shimmer`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScratchFoil {
+    pub color: Color,
+    /// Speed of the light sweeping over the card, in radians per second
+    pub speed: f32,
+    /// Times [`ScratchCardMaterial::foil_map`] repeats across the card
+    pub map_scale: f32,
+    /// Specular exponent, higher values give smaller, crisper glints
+    pub sharpness: f32,
+}
+
+impl Default for ScratchFoil {
+    fn default() -> Self {
+        Self {
+            color: Color::WHITE,
+            speed: DEFAULT_FOIL_SPEED,
+            map_scale: DEFAULT_FOIL_MAP_SCALE,
+            sharpness: DEFAULT_FOIL_SHARPNESS,
+        }
+    }
+}
+
+impl Default for ScratchCover {
+    fn default() -> Self {
+        Self {
+            tiling: Vec2::splat(DEFAULT_COVER_TILING),
+            offset: Vec2::ZERO,
+            tint: Color::WHITE,
+            shimmer: 0.0,
+            foil: ScratchFoil::default(),
+            reveal_alpha: ScratchRevealAlpha::Texture,
+        }
+    }
+}
+
+/// Shading along the edges of scratched holes so they look cut into the foil, off by default
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScratchEdge {
+    /// Distance in mask pixels the edge is measured over
+    pub width: f32,
+    /// How much the cover darkens along the rim of a hole, in `0..=1`
+    pub rim_darkness: f32,
+    /// Strength of the light catching the rim
+    pub highlight: f32,
+    pub highlight_color: Color,
+    /// Direction the light comes from, in card UV (y down)
+    pub light_direction: Vec2,
+    /// How much the cover darkens the revealed area next to it, in `0..=1`
+    pub shadow: f32,
+    /// How far the shadow falls, in mask pixels (y down)
+    pub shadow_offset: Vec2,
+}
+
+impl Default for ScratchEdge {
+    fn default() -> Self {
+        Self {
+            width: DEFAULT_EDGE_WIDTH,
+            rim_darkness: 0.0,
+            highlight: 0.0,
+            highlight_color: Color::WHITE,
+            light_direction: Vec2::new(-1.0, -1.0),
+            shadow: 0.0,
+            shadow_offset: Vec2::splat(DEFAULT_EDGE_WIDTH),
+        }
+    }
+}
+
+/// Material blending the cover layer and the reveal texture through the scratch mask
+#[derive(Asset, AsBindGroup, TypePath, Debug, Clone)]
+#[uniform(7, ScratchCoverUniform)]
+#[uniform(10, ScratchEdgeUniform)]
+pub struct ScratchCardMaterial {
+    #[texture(0)]
+    #[sampler(1)]
+    pub reveal_texture: Handle<Image>,
+    #[texture(2)]
+    #[sampler(3)]
+    pub scratch_mask: Handle<Image>,
+    #[texture(4)]
+    #[sampler(5)]
+    pub cover_layer: Handle<Image>,
+    /// Animated dissolve of whatever cover is left, see [`super::ScratchRevealAnimation`]
+    #[uniform(6)]
+    pub reveal: ScratchRevealUniform,
+    pub cover: ScratchCover,
+    /// Normal or noise map tilting the foil highlight, a flat foil without one
+    #[texture(8)]
+    #[sampler(9)]
+    pub foil_map: Option<Handle<Image>>,
+    pub edge: ScratchEdge,
+}
+
+impl ScratchCardMaterial {
+    pub fn new(
+        reveal_texture: Handle<Image>,
+        scratch_mask: Handle<Image>,
+        cover_layer: Handle<Image>,
+    ) -> Self {
+        Self {
+            reveal_texture,
+            scratch_mask,
+            cover_layer,
+            reveal: ScratchRevealUniform::default(),
+            cover: ScratchCover::default(),
+            foil_map: None,
+            edge: ScratchEdge::default(),
+        }
+    }
+}
+
+impl AsBindGroupShaderType<ScratchCoverUniform> for ScratchCardMaterial {
+    fn as_bind_group_shader_type(&self, _images: &RenderAssets<GpuImage>) -> ScratchCoverUniform {
+        ScratchCoverUniform {
+            tint: self.cover.tint.to_linear().to_vec4(),
+            tiling: self.cover.tiling,
+            offset: self.cover.offset,
+            shimmer: self.cover.shimmer,
+            foil_color: self.cover.foil.color.to_linear().to_vec4(),
+            foil_speed: self.cover.foil.speed,
+            foil_map_scale: self.cover.foil.map_scale,
+            foil_sharpness: self.cover.foil.sharpness,
+            has_foil_map: self.foil_map.is_some() as u32,
+            transparent_reveal: (self.cover.reveal_alpha == ScratchRevealAlpha::Transparent) as u32,
+        }
+    }
+}
+
+impl AsBindGroupShaderType<ScratchEdgeUniform> for ScratchCardMaterial {
+    fn as_bind_group_shader_type(&self, _images: &RenderAssets<GpuImage>) -> ScratchEdgeUniform {
+        ScratchEdgeUniform {
+            highlight_color: self.edge.highlight_color.to_linear().to_vec4(),
+            light_direction: self.edge.light_direction.normalize_or_zero(),
+            shadow_offset: self.edge.shadow_offset,
+            width: self.edge.width,
+            rim_darkness: self.edge.rim_darkness,
+            highlight: self.edge.highlight,
+            shadow: self.edge.shadow,
+        }
+    }
+}
+
+impl Material2d for ScratchCardMaterial {
+    fn fragment_shader() -> ShaderRef {
+        SCRATCH_CARD_SHADER_PATH.into()
+    }
+
+    fn vertex_shader() -> ShaderRef {
+        ShaderRef::Default
+    }
+
+    fn alpha_mode(&self) -> AlphaMode2d {
+        AlphaMode2d::Blend
+    }
+}
+
+// `ShaderType` checks field layouts in functions nothing calls, each uniform gets a module
+// so the allow covers only its own derive
+#[allow(dead_code)]
+mod scratch_reveal_uniform {
+    use bevy::{prelude::*, render::render_resource::ShaderType};
+
+    /// Parameters of the reveal animation as seen by `scratch_card.wgsl`
+    #[derive(ShaderType, Debug, Clone, Copy, Default)]
+    pub struct ScratchRevealUniform {
+        /// Radial wipe centre in card UV
+        pub origin: Vec2,
+        /// `0` leaves the mask alone, `1` uncovers the whole card
+        pub progress: f32,
+        /// Width of the dissolving edge, in the same units as the style's threshold
+        pub softness: f32,
+        /// Noise cells across the card for the noise dissolve
+        pub noise_scale: f32,
+        /// 0 = fade, 1 = radial wipe, 2 = noise dissolve
+        pub style: u32,
+    }
+}
+
+#[allow(dead_code)]
+mod scratch_cover_uniform {
+    use bevy::{prelude::*, render::render_resource::ShaderType};
+
+    /// Cover settings as seen by `scratch_card.wgsl`, built from [`super::ScratchCover`]
+    #[derive(ShaderType, Debug, Clone, Copy)]
+    pub struct ScratchCoverUniform {
+        pub tint: Vec4,
+        pub tiling: Vec2,
+        pub offset: Vec2,
+        pub shimmer: f32,
+        pub foil_color: Vec4,
+        pub foil_speed: f32,
+        pub foil_map_scale: f32,
+        pub foil_sharpness: f32,
+        pub has_foil_map: u32,
+        pub transparent_reveal: u32,
+    }
+}
+
+#[allow(dead_code)]
+mod scratch_edge_uniform {
+    use bevy::{prelude::*, render::render_resource::ShaderType};
+
+    /// Edge settings as seen by `scratch_card.wgsl`, built from [`super::ScratchEdge`]
+    #[derive(ShaderType, Debug, Clone, Copy)]
+    pub struct ScratchEdgeUniform {
+        pub highlight_color: Vec4,
+        pub light_direction: Vec2,
+        pub shadow_offset: Vec2,
+        pub width: f32,
+        pub rim_darkness: f32,
+        pub highlight: f32,
+        pub shadow: f32,
+    }
+}
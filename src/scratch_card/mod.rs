@@ -0,0 +1,196 @@
+//! Reusable scratch card: spawn an entity with a [`ScratchCard`] and the
+//! [`ScratchCardPlugin`] attaches its quad, material and mask.
+
+mod brush;
+mod debris;
+mod gpu;
+mod history;
+mod input;
+mod layout;
+mod mask;
+mod material;
+mod persist;
+mod progress;
+mod region;
+mod reveal;
+mod stroke;
+mod stroke_log;
+mod upload;
+
+use bevy::{prelude::*, render::render_resource::TextureUsages, sprite::Material2dPlugin};
+
+pub use brush::{BrushFootprint, ScratchBlend, ScratchBrush};
+pub use debris::{ScratchDebris, ScratchDebrisParticle};
+pub use gpu::ScratchGpuSupport;
+pub use history::ScratchHistory;
+pub use layout::{
+    PrizeMatched, PrizeOutcome, PrizeSlotUncovered, PrizeSymbol, ScratchCardLayout, ScratchPrizes,
+};
+pub use mask::{
+    MAX_STAMP_OFFSET, MAX_STAMP_RADIUS, REVEALED_VALUE, ScratchMask, ScratchStamp,
+    create_mask_image,
+};
+pub use material::{
+    ScratchCardMaterial, ScratchCover, ScratchEdge, ScratchFoil, ScratchRevealAlpha,
+    ScratchRevealUniform,
+};
+pub use persist::{ScratchMaskRestore, ScratchMaskSnapshot, ScratchSnapshotError};
+pub use progress::{ScratchCompleted, ScratchProgress, ScratchStarted, ScratchThresholdReached};
+pub use region::{RegionShape, ScratchRegion, ScratchRegionRevealed, ScratchRegions};
+pub use reveal::{ScratchRevealAnimation, ScratchRevealStyle};
+pub use stroke::{ScratchPointer, ScratchStrokes, StrokeInterpolator};
+pub use stroke_log::{LoggedStamp, ScratchLogError, ScratchStrokeLog};
+
+const DEFAULT_CARD_SIZE: f32 = 512.0;
+
+pub struct ScratchCardPlugin;
+
+impl Plugin for ScratchCardPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            Material2dPlugin::<ScratchCardMaterial>::default(),
+            upload::ScratchMaskUploadPlugin,
+            gpu::ScratchComputePlugin,
+        ))
+        .add_event::<ScratchStarted>()
+        .add_event::<ScratchThresholdReached>()
+        .add_event::<ScratchCompleted>()
+        .add_event::<PrizeSlotUncovered>()
+        .add_event::<PrizeMatched>()
+        .add_event::<ScratchRegionRevealed>()
+        .add_systems(
+            Update,
+            (
+                setup_scratch_cards,
+                layout::composite_prize_layouts,
+                persist::apply_mask_restores,
+                input::handle_pointer_input,
+                reveal::animate_reveals,
+                history::drop_scratch_history,
+                history::record_scratch_history,
+                progress::update_scratch_progress,
+                layout::update_prize_slots,
+                region::update_scratch_regions,
+                debris::spawn_debris,
+                debris::update_debris,
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Where a card's mask texture gets painted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScratchBackend {
+    /// Paint on the CPU and upload the dirty rectangle
+    #[default]
+    Cpu,
+    /// Dispatch `scratch_update.wgsl` against the mask, falls back to
+    /// [`ScratchBackend::Cpu`] when the device can't write `r8unorm` storage textures
+    /// or the shader fails to compile
+    Gpu,
+}
+
+/// A scratchable card, `size` and `mask_scale` are read once when the card is set up
+#[derive(Component, Debug, Clone)]
+#[require(Transform, Visibility)]
+pub struct ScratchCard {
+    pub reveal_texture: Handle<Image>,
+    pub cover_texture: Handle<Image>,
+    /// Normal or noise map for the cover's foil highlight, see [`ScratchCover::shimmer`]
+    pub foil_map: Option<Handle<Image>>,
+    /// Width and height of the card's quad in card units, world units before its transform
+    pub size: Vec2,
+    /// Mask pixels per card unit, e.g. `0.25` for a quarter resolution mask on a large card
+    pub mask_scale: f32,
+    pub brush: ScratchBrush,
+    pub backend: ScratchBackend,
+    /// Revealed fractions that emit [`ScratchThresholdReached`], e.g. `0.7` to auto-reveal the rest
+    pub reveal_thresholds: Vec<f32>,
+    /// Copied to the card's [`ScratchCardMaterial`] when it is set up
+    pub cover: ScratchCover,
+    /// Shading around scratched holes, copied like [`ScratchCard::cover`]
+    pub edge: ScratchEdge,
+}
+
+impl ScratchCard {
+    /// Mask width and height in pixels
+    pub fn mask_size(&self) -> UVec2 {
+        (self.size * self.mask_scale)
+            .ceil()
+            .max(Vec2::ONE)
+            .as_uvec2()
+    }
+
+    pub fn new(reveal_texture: Handle<Image>, cover_texture: Handle<Image>) -> Self {
+        Self {
+            reveal_texture,
+            cover_texture,
+            foil_map: None,
+            size: Vec2::splat(DEFAULT_CARD_SIZE),
+            mask_scale: 1.0,
+            brush: ScratchBrush::default(),
+            backend: ScratchBackend::default(),
+            reveal_thresholds: Vec::new(),
+            cover: ScratchCover::default(),
+            edge: ScratchEdge::default(),
+        }
+    }
+}
+
+/// Attach mesh, material and a fully covered mask to newly spawned cards
+fn setup_scratch_cards(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ScratchCardMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+    gpu_support: Option<Res<ScratchGpuSupport>>,
+    cards: Query<(Entity, &ScratchCard), Without<ScratchMask>>,
+) {
+    let gpu_supported = gpu_support.is_some_and(|support| support.0);
+
+    for (entity, card) in &cards {
+        let backend = match card.backend {
+            ScratchBackend::Gpu if gpu_supported => ScratchBackend::Gpu,
+            _ => ScratchBackend::Cpu,
+        };
+
+        // Create initial black mask (0 = show cover layer), kept for the card's lifetime
+        let mask_size = card.mask_size();
+        let mut mask_image =
+            create_mask_image(&vec![0u8; mask_size.element_product() as usize], mask_size);
+        if backend == ScratchBackend::Gpu {
+            mask_image.texture_descriptor.usage |= TextureUsages::STORAGE_BINDING;
+        }
+        let mask_image = images.add(mask_image);
+        let mask = ScratchMask::new(mask_size, mask_image.clone(), backend);
+
+        let material = materials.add(ScratchCardMaterial {
+            cover: card.cover.clone(),
+            foil_map: card.foil_map.clone(),
+            edge: card.edge.clone(),
+            ..ScratchCardMaterial::new(
+                card.reveal_texture.clone(),
+                mask_image,
+                card.cover_texture.clone(),
+            )
+        });
+
+        commands.entity(entity).insert((
+            Mesh2d(meshes.add(Rectangle::from_size(card.size))),
+            MeshMaterial2d(material),
+            mask,
+            ScratchStrokes::default(),
+            ScratchProgress::default(),
+        ));
+    }
+}
+
+/// Small, well distributed generator, so seeded results don't depend on an RNG crate version
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,224 @@
+use std::{fmt, io, path::Path};
+
+use bevy::prelude::*;
+
+use super::{ScratchMask, mask::count_revealed};
+
+const SNAPSHOT_MAGIC: &[u8; 4] = b"SCRM";
+const SNAPSHOT_VERSION: u8 = 2;
+/// Version 1 snapshots store a single size for square masks
+const SQUARE_SNAPSHOT_VERSION: u8 = 1;
+/// Magic and version, followed by the size and revealed count
+const PREAMBLE_LEN: usize = 4 + 1;
+
+/// Saved state of a card's mask, stored as run-length encoded bytes.
+///
+/// ```
+/// use bevy::prelude::*;
+/// use mask_2d::scratch_card::{ScratchMaskSnapshot, ScratchSnapshotError};
+///
+/// let mut data = vec![0u8; 64 * 64];
+/// data[100..300].fill(255);
+/// data[1000] = 17;
+/// let snapshot = ScratchMaskSnapshot::new(UVec2::new(64, 64), data.clone());
+///
+/// let restored = ScratchMaskSnapshot::from_bytes(&snapshot.to_bytes()).unwrap();
+/// assert_eq!(restored.data, data);
+/// assert_eq!(restored.revealed, 200);
+///
+/// // A header claiming a huge mask without the data for it is rejected, not allocated
+/// let mut forged = b"SCRM\x02".to_vec();
+/// forged.extend_from_slice(&[0xFF; 12]);
+/// assert!(matches!(
+///     ScratchMaskSnapshot::from_bytes(&forged),
+///     Err(ScratchSnapshotError::Corrupt)
+/// ));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScratchMaskSnapshot {
+    /// Width and height in mask pixels
+    pub size: UVec2,
+    /// Pixels at or above [`super::REVEALED_VALUE`], checked again when loading
+    pub revealed: u32,
+    pub data: Vec<u8>,
+}
+
+/// Why a snapshot couldn't be read or applied
+#[derive(Debug)]
+pub enum ScratchSnapshotError {
+    Io(io::Error),
+    /// The bytes don't start with the snapshot magic
+    NotASnapshot,
+    UnsupportedVersion(u8),
+    /// The run-length data ends early, overflows the mask or disagrees with the header
+    Corrupt,
+    /// The snapshot was taken from a mask of another size
+    SizeMismatch {
+        expected: UVec2,
+        found: UVec2,
+    },
+}
+
+impl fmt::Display for ScratchSnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "failed to access scratch mask snapshot: {err}"),
+            Self::NotASnapshot => write!(f, "data is not a scratch mask snapshot"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported scratch mask snapshot version {version}")
+            }
+            Self::Corrupt => write!(f, "scratch mask snapshot is corrupt"),
+            Self::SizeMismatch { expected, found } => write!(
+                f,
+                "scratch mask snapshot is {}x{} pixels, expected {}x{}",
+                found.x, found.y, expected.x, expected.y
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ScratchSnapshotError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ScratchSnapshotError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl ScratchMaskSnapshot {
+    /// Snapshot of raw mask bytes, `data` must hold `size.x * size.y` values
+    pub fn new(size: UVec2, data: Vec<u8>) -> Self {
+        Self {
+            size,
+            revealed: count_revealed(&data),
+            data,
+        }
+    }
+
+    /// Encode as header followed by `(run length u16 LE, value)` pairs
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(PREAMBLE_LEN + 12 + 64);
+        bytes.extend_from_slice(SNAPSHOT_MAGIC);
+        bytes.push(SNAPSHOT_VERSION);
+        bytes.extend_from_slice(&self.size.x.to_le_bytes());
+        bytes.extend_from_slice(&self.size.y.to_le_bytes());
+        bytes.extend_from_slice(&self.revealed.to_le_bytes());
+
+        let mut values = self.data.iter().copied().peekable();
+        while let Some(value) = values.next() {
+            let mut run: u16 = 1;
+            while run < u16::MAX && values.peek() == Some(&value) {
+                values.next();
+                run += 1;
+            }
+            bytes.extend_from_slice(&run.to_le_bytes());
+            bytes.push(value);
+        }
+        bytes
+    }
+
+    /// Read a snapshot, version 1 (square) snapshots are still accepted
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ScratchSnapshotError> {
+        if bytes.len() < PREAMBLE_LEN || &bytes[..4] != SNAPSHOT_MAGIC {
+            return Err(ScratchSnapshotError::NotASnapshot);
+        }
+        let u32_at = |offset: usize| {
+            bytes
+                .get(offset..offset + 4)
+                .map(|le| u32::from_le_bytes(le.try_into().unwrap()))
+                .ok_or(ScratchSnapshotError::Corrupt)
+        };
+        let (size, header_len) = match bytes[4] {
+            SNAPSHOT_VERSION => (UVec2::new(u32_at(5)?, u32_at(9)?), PREAMBLE_LEN + 12),
+            SQUARE_SNAPSHOT_VERSION => (UVec2::splat(u32_at(5)?), PREAMBLE_LEN + 8),
+            version => return Err(ScratchSnapshotError::UnsupportedVersion(version)),
+        };
+        let revealed = u32_at(header_len - 4)?;
+
+        // The header can't be trusted with an allocation, each 3 byte run fills at most `u16::MAX`
+        let max_len = ((bytes.len() - header_len) / 3).saturating_mul(u16::MAX as usize);
+        let len = (size.x as usize)
+            .checked_mul(size.y as usize)
+            .filter(|&len| len <= max_len)
+            .ok_or(ScratchSnapshotError::Corrupt)?;
+        let mut data = Vec::with_capacity(len);
+        for pair in bytes[header_len..].chunks(3) {
+            let &[lo, hi, value] = pair else {
+                return Err(ScratchSnapshotError::Corrupt);
+            };
+            let run = u16::from_le_bytes([lo, hi]) as usize;
+            if data.len() + run > len {
+                return Err(ScratchSnapshotError::Corrupt);
+            }
+            data.resize(data.len() + run, value);
+        }
+        if data.len() != len || count_revealed(&data) != revealed {
+            return Err(ScratchSnapshotError::Corrupt);
+        }
+
+        Ok(Self {
+            size,
+            revealed,
+            data,
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ScratchSnapshotError> {
+        std::fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ScratchSnapshotError> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+}
+
+impl ScratchMask {
+    /// Capture the current mask so it can be saved
+    pub fn snapshot(&self) -> ScratchMaskSnapshot {
+        ScratchMaskSnapshot {
+            size: self.size,
+            revealed: self.revealed(),
+            data: self.data.clone(),
+        }
+    }
+
+    /// Replace the mask with a saved one and re-upload it
+    pub fn restore(&mut self, snapshot: &ScratchMaskSnapshot) -> Result<(), ScratchSnapshotError> {
+        if snapshot.size != self.size || snapshot.data.len() != self.data.len() {
+            return Err(ScratchSnapshotError::SizeMismatch {
+                expected: self.size,
+                found: snapshot.size,
+            });
+        }
+        self.before_edit(URect::from_corners(UVec2::ZERO, self.size));
+        self.data.copy_from_slice(&snapshot.data);
+        self.mark_all_dirty();
+        Ok(())
+    }
+}
+
+/// Spawn alongside a [`super::ScratchCard`] (or insert later) to restore a saved mask.
+///
+/// The progress events for the restored amount fire again, as if scratched this session.
+#[derive(Component, Debug, Clone)]
+pub struct ScratchMaskRestore(pub ScratchMaskSnapshot);
+
+pub(super) fn apply_mask_restores(
+    mut commands: Commands,
+    mut cards: Query<(Entity, &mut ScratchMask, &ScratchMaskRestore)>,
+) {
+    for (entity, mut mask, restore) in &mut cards {
+        if let Err(err) = mask.restore(&restore.0) {
+            warn!("Could not restore scratch card {entity}: {err}");
+        }
+        commands.entity(entity).remove::<ScratchMaskRestore>();
+    }
+}
@@ -0,0 +1,81 @@
+use bevy::prelude::*;
+
+use super::{ScratchCard, ScratchMask};
+
+/// How much of a card has been scratched, kept in sync with its [`ScratchMask`]
+#[derive(Component, Debug, Clone, Default)]
+pub struct ScratchProgress {
+    /// Pixels at or above [`super::REVEALED_VALUE`]
+    pub revealed: u32,
+    /// Pixels in the mask
+    pub total: u32,
+    /// Highest fraction seen so far, so events fire once even if the mask is reverted
+    peak: f32,
+}
+
+impl ScratchProgress {
+    /// Fraction of the card that is revealed, in `0..=1`
+    pub fn fraction(&self) -> f32 {
+        self.revealed as f32 / self.total.max(1) as f32
+    }
+}
+
+/// The first pixel of a card was scratched
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ScratchStarted {
+    pub card: Entity,
+}
+
+/// A card's revealed fraction reached one of its [`ScratchCard::reveal_thresholds`]
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ScratchThresholdReached {
+    pub card: Entity,
+    pub threshold: f32,
+}
+
+/// Every pixel of a card is revealed
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ScratchCompleted {
+    pub card: Entity,
+}
+
+pub(super) fn update_scratch_progress(
+    mut cards: Query<
+        (Entity, &ScratchCard, &ScratchMask, &mut ScratchProgress),
+        Changed<ScratchMask>,
+    >,
+    mut started: EventWriter<ScratchStarted>,
+    mut thresholds: EventWriter<ScratchThresholdReached>,
+    mut completed: EventWriter<ScratchCompleted>,
+) {
+    for (entity, card, mask, mut progress) in &mut cards {
+        let total = mask.data.len() as u32;
+        if progress.revealed == mask.revealed() && progress.total == total {
+            continue;
+        }
+        progress.revealed = mask.revealed();
+        progress.total = total;
+
+        let fraction = progress.fraction();
+        let peak = progress.peak;
+        if fraction <= peak {
+            continue;
+        }
+        progress.peak = fraction;
+
+        if peak == 0.0 {
+            started.write(ScratchStarted { card: entity });
+        }
+        for &threshold in &card.reveal_thresholds {
+            if peak < threshold && threshold <= fraction {
+                thresholds.write(ScratchThresholdReached {
+                    card: entity,
+                    threshold,
+                });
+            }
+        }
+        if progress.revealed == total {
+            completed.write(ScratchCompleted { card: entity });
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use bevy::prelude::*;
+
+use super::{ScratchMask, layout::uv_rect_to_pixels, mask::count_revealed};
+
+/// Area of a card in UV (`0..=1`, y down)
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegionShape {
+    Rect(Rect),
+    /// Closed polygon, a pixel belongs to it when its centre is inside (even-odd rule)
+    Polygon(Vec<Vec2>),
+}
+
+impl RegionShape {
+    /// Revealed and total mask pixels in the shape, parts outside the mask don't count
+    pub fn coverage(&self, mask: &ScratchMask) -> (u32, u32) {
+        match self {
+            Self::Rect(rect) => {
+                let pixels = uv_rect_to_pixels(*rect, mask.size);
+                (mask.revealed_in(pixels), pixels.width() * pixels.height())
+            }
+            Self::Polygon(points) => polygon_coverage(mask, points),
+        }
+    }
+}
+
+/// A named part of a card with its own reveal threshold
+#[derive(Debug, Clone)]
+pub struct ScratchRegion {
+    pub name: String,
+    pub shape: RegionShape,
+    /// Revealed fraction of the region that emits [`ScratchRegionRevealed`]
+    pub threshold: f32,
+    reached: bool,
+}
+
+impl ScratchRegion {
+    pub fn new(name: impl Into<String>, shape: RegionShape, threshold: f32) -> Self {
+        Self {
+            name: name.into(),
+            shape,
+            threshold,
+            reached: false,
+        }
+    }
+
+    pub fn rect(name: impl Into<String>, rect: Rect, threshold: f32) -> Self {
+        Self::new(name, RegionShape::Rect(rect), threshold)
+    }
+
+    pub fn polygon(name: impl Into<String>, points: Vec<Vec2>, threshold: f32) -> Self {
+        Self::new(name, RegionShape::Polygon(points), threshold)
+    }
+
+    /// Whether [`ScratchRegionRevealed`] has fired for this region
+    pub fn is_reached(&self) -> bool {
+        self.reached
+    }
+
+    /// Fraction of the region's mask pixels that are revealed, `0` for an empty region
+    pub fn revealed_fraction(&self, mask: &ScratchMask) -> f32 {
+        let (revealed, total) = self.shape.coverage(mask);
+        revealed as f32 / total.max(1) as f32
+    }
+
+    /// Mark the region reached once enough of it is revealed, `true` only the first time.
+    ///
+    /// A region without mask pixels is never reached.
+    pub(super) fn reach(&mut self, mask: &ScratchMask) -> bool {
+        if self.reached {
+            return false;
+        }
+        let (revealed, total) = self.shape.coverage(mask);
+        self.reached = total > 0 && revealed as f32 >= self.threshold * total as f32;
+        self.reached
+    }
+}
+
+/// Named regions of a card, each firing [`ScratchRegionRevealed`] once
+#[derive(Component, Debug, Clone, Default)]
+pub struct ScratchRegions(pub Vec<ScratchRegion>);
+
+/// A region's revealed fraction reached its [`ScratchRegion::threshold`]
+#[derive(Event, Debug, Clone)]
+pub struct ScratchRegionRevealed {
+    pub card: Entity,
+    /// Index into the card's [`ScratchRegions`]
+    pub region: usize,
+    pub name: String,
+}
+
+/// Revealed and total pixels whose centres lie inside the polygon, scanned row by row
+fn polygon_coverage(mask: &ScratchMask, points: &[Vec2]) -> (u32, u32) {
+    if points.len() < 3 {
+        return (0, 0);
+    }
+    let size = mask.size.as_vec2();
+    let points = points.iter().map(|point| *point * size).collect::<Vec<_>>();
+    let (min_y, max_y) = points.iter().fold((f32::MAX, f32::MIN), |(lo, hi), p| {
+        (lo.min(p.y), hi.max(p.y))
+    });
+
+    let mut revealed = 0;
+    let mut total = 0;
+    let mut crossings = Vec::new();
+    let first_row = min_y.floor().max(0.0) as u32;
+    let last_row = (max_y.ceil().max(0.0) as u32).min(mask.size.y);
+    for y in first_row..last_row {
+        let centre_y = y as f32 + 0.5;
+        crossings.clear();
+        for (a, b) in points.iter().zip(points.iter().cycle().skip(1)) {
+            if (a.y <= centre_y) != (b.y <= centre_y) {
+                crossings.push(a.x + (centre_y - a.y) / (b.y - a.y) * (b.x - a.x));
+            }
+        }
+        crossings.sort_by(f32::total_cmp);
+
+        let row = &mask.data[(y * mask.size.x) as usize..][..mask.size.x as usize];
+        for span in crossings.chunks_exact(2) {
+            // Pixels whose centre x lies in `span[0]..span[1]`
+            let start = (span[0] - 0.5).ceil().clamp(0.0, size.x) as usize;
+            let end = (span[1] - 0.5).ceil().clamp(0.0, size.x) as usize;
+            if start < end {
+                total += (end - start) as u32;
+                revealed += count_revealed(&row[start..end]);
+            }
+        }
+    }
+    (revealed, total)
+}
+
+pub(super) fn update_scratch_regions(
+    mut cards: Query<(Entity, &ScratchMask, &mut ScratchRegions), Changed<ScratchMask>>,
+    mut revealed: EventWriter<ScratchRegionRevealed>,
+) {
+    for (entity, mask, mut regions) in &mut cards {
+        for (index, region) in regions.0.iter_mut().enumerate() {
+            if !region.reach(mask) {
+                continue;
+            }
+            revealed.write(ScratchRegionRevealed {
+                card: entity,
+                region: index,
+                name: region.name.clone(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scratch_card::ScratchBackend;
+
+    /// 4x4 mask with the given pixels revealed
+    fn mask(revealed: &[(u32, u32)]) -> ScratchMask {
+        let mut mask = ScratchMask::new(UVec2::splat(4), Handle::default(), ScratchBackend::Cpu);
+        for &(x, y) in revealed {
+            mask.data[(y * 4 + x) as usize] = 255;
+        }
+        mask
+    }
+
+    /// Polygon given in mask pixels, as UV
+    fn polygon(points: &[(f32, f32)]) -> Vec<Vec2> {
+        points.iter().map(|&(x, y)| Vec2::new(x, y) / 4.0).collect()
+    }
+
+    #[test]
+    fn polygon_counts_pixel_centres() {
+        let mask = mask(&[(0, 0), (2, 0)]);
+        // Centres at x = 0.5 and 1.5 fall in `0.5..2`, the one at 2.5 doesn't
+        let strip = polygon(&[(0.5, 0.0), (2.0, 0.0), (2.0, 4.0), (0.5, 4.0)]);
+        assert_eq!(polygon_coverage(&mask, &strip), (1, 8));
+        // Overlaps two columns of pixels but none of their centres
+        let sliver = polygon(&[(0.6, 0.0), (1.4, 0.0), (1.4, 4.0), (0.6, 4.0)]);
+        assert_eq!(polygon_coverage(&mask, &sliver), (0, 0));
+    }
+
+    #[test]
+    fn concave_polygon_skips_its_notch() {
+        // An upside down U: the top row and the outer columns below it
+        let arch = polygon(&[
+            (0.0, 0.0),
+            (4.0, 0.0),
+            (4.0, 4.0),
+            (3.0, 4.0),
+            (3.0, 1.0),
+            (1.0, 1.0),
+            (1.0, 4.0),
+            (0.0, 4.0),
+        ]);
+        let mask = mask(&[(0, 2), (1, 2), (2, 3), (3, 3)]);
+        assert_eq!(polygon_coverage(&mask, &arch), (2, 10));
+    }
+
+    #[test]
+    fn polygon_outside_the_mask_is_clipped() {
+        let mask = mask(&[(3, 3)]);
+        let around = polygon(&[(-4.0, -4.0), (8.0, -4.0), (8.0, 8.0), (-4.0, 8.0)]);
+        assert_eq!(polygon_coverage(&mask, &around), (1, 16));
+        let beside = polygon(&[(5.0, 0.0), (8.0, 0.0), (8.0, 4.0), (5.0, 4.0)]);
+        assert_eq!(polygon_coverage(&mask, &beside), (0, 0));
+        let above = polygon(&[(0.0, -4.0), (4.0, -4.0), (4.0, -1.0)]);
+        assert_eq!(polygon_coverage(&mask, &above), (0, 0));
+    }
+}
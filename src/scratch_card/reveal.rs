@@ -0,0 +1,93 @@
+use bevy::prelude::*;
+
+use super::{ScratchCardMaterial, ScratchMask, material::ScratchRevealUniform};
+
+const DEFAULT_REVEAL_SOFTNESS: f32 = 0.15;
+
+/// How the remaining cover disappears during a [`ScratchRevealAnimation`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScratchRevealStyle {
+    /// The whole cover fades out evenly
+    Fade,
+    /// A circle grows from a point in card UV
+    RadialWipe { origin: Vec2 },
+    /// The cover breaks up into noise blotches of `scale` cells across the card
+    NoiseDissolve { scale: f32 },
+}
+
+/// Insert on a card to dissolve the rest of its cover over `duration` seconds.
+///
+/// The mask is filled once the animation ends, so progress events still fire
+/// and the component is removed.
+#[derive(Component, Debug, Clone)]
+pub struct ScratchRevealAnimation {
+    pub style: ScratchRevealStyle,
+    pub duration: f32,
+    /// Width of the dissolving edge as a fraction of the animation's range
+    pub softness: f32,
+    elapsed: f32,
+}
+
+impl ScratchRevealAnimation {
+    pub fn new(style: ScratchRevealStyle, duration: f32) -> Self {
+        Self {
+            style,
+            duration,
+            softness: DEFAULT_REVEAL_SOFTNESS,
+            elapsed: 0.0,
+        }
+    }
+
+    /// Animation progress in `0..=1`
+    pub fn progress(&self) -> f32 {
+        if self.duration <= 0.0 {
+            return 1.0;
+        }
+        (self.elapsed / self.duration).clamp(0.0, 1.0)
+    }
+
+    fn uniform(&self) -> ScratchRevealUniform {
+        let (style, origin, noise_scale) = match self.style {
+            ScratchRevealStyle::Fade => (0, Vec2::ZERO, 0.0),
+            ScratchRevealStyle::RadialWipe { origin } => (1, origin, 0.0),
+            ScratchRevealStyle::NoiseDissolve { scale } => (2, Vec2::ZERO, scale),
+        };
+        ScratchRevealUniform {
+            origin,
+            progress: self.progress(),
+            softness: self.softness,
+            noise_scale,
+            style,
+        }
+    }
+}
+
+/// Advance reveal animations on their card materials, filling the mask when they finish
+pub(super) fn animate_reveals(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut materials: ResMut<Assets<ScratchCardMaterial>>,
+    mut cards: Query<(
+        Entity,
+        &mut ScratchRevealAnimation,
+        &MeshMaterial2d<ScratchCardMaterial>,
+        &mut ScratchMask,
+    )>,
+) {
+    for (entity, mut animation, material, mut mask) in &mut cards {
+        animation.elapsed += time.delta_secs();
+        let Some(material) = materials.get_mut(&material.0) else {
+            continue;
+        };
+
+        if animation.progress() < 1.0 {
+            material.reveal = animation.uniform();
+            continue;
+        }
+
+        // The filled mask shows the same thing, so the uniform can go back to idle
+        mask.reveal_all();
+        material.reveal = ScratchRevealUniform::default();
+        commands.entity(entity).remove::<ScratchRevealAnimation>();
+    }
+}
@@ -0,0 +1,175 @@
+use bevy::{platform::collections::HashMap, prelude::*};
+
+/// Turns pointer samples in mask pixel space into evenly spaced stamp centres,
+/// so a fast drag leaves a continuous trail instead of isolated dots
+#[derive(Debug, Clone, Default)]
+pub struct StrokeInterpolator {
+    /// Most recent samples, oldest first, at most four
+    samples: Vec<Vec2>,
+}
+
+impl StrokeInterpolator {
+    /// Whether a stroke is in progress
+    pub fn is_active(&self) -> bool {
+        !self.samples.is_empty()
+    }
+
+    /// Add a sample and return the stamp centres needed to reach it.
+    ///
+    /// With `smooth` the path is a Catmull-Rom spline through the samples,
+    /// which lags one sample behind until [`StrokeInterpolator::end`].
+    pub fn push(&mut self, point: Vec2, spacing: f32, smooth: bool) -> Vec<Vec2> {
+        if self.samples.last() == Some(&point) {
+            return Vec::new();
+        }
+        self.samples.push(point);
+
+        let n = self.samples.len();
+        let centres = if n == 1 {
+            // The first sample stamps immediately
+            vec![point]
+        } else if !smooth {
+            line(self.samples[n - 2], self.samples[n - 1], spacing)
+        } else if n == 2 {
+            // Need the following sample to shape the first segment
+            Vec::new()
+        } else {
+            // The first segment has no earlier sample and reuses its own start
+            let prev = self.samples[n.saturating_sub(4)];
+            catmull_rom(
+                prev,
+                self.samples[n - 3],
+                self.samples[n - 2],
+                self.samples[n - 1],
+                spacing,
+            )
+        };
+
+        if self.samples.len() == 4 {
+            self.samples.remove(0);
+        }
+        centres
+    }
+
+    /// Finish the stroke, returning the stamps of a segment still held back by smoothing
+    pub fn end(&mut self, spacing: f32, smooth: bool) -> Vec<Vec2> {
+        let samples = std::mem::take(&mut self.samples);
+        let n = samples.len();
+        if !smooth || n < 2 {
+            return Vec::new();
+        }
+        let prev = if n >= 3 {
+            samples[n - 3]
+        } else {
+            samples[n - 2]
+        };
+        catmull_rom(
+            prev,
+            samples[n - 2],
+            samples[n - 1],
+            samples[n - 1],
+            spacing,
+        )
+    }
+}
+
+/// Stamp centres from `a` (exclusive) to `b` (inclusive)
+fn line(a: Vec2, b: Vec2, spacing: f32) -> Vec<Vec2> {
+    let steps = (a.distance(b) / spacing.max(1.0)).ceil().max(1.0) as u32;
+    (1..=steps)
+        .map(|i| a.lerp(b, i as f32 / steps as f32))
+        .collect()
+}
+
+/// Stamp centres along the Catmull-Rom segment from `p1` (exclusive) to `p2` (inclusive)
+fn catmull_rom(p0: Vec2, p1: Vec2, p2: Vec2, p3: Vec2, spacing: f32) -> Vec<Vec2> {
+    // As a Bézier the segment has legs `p1 -> b1 -> b2 -> p2`, its speed never exceeds three
+    // times the longest leg, so equal steps in `t` stay within `spacing` of each other
+    let b1 = p1 + (p2 - p0) / 6.0;
+    let b2 = p2 - (p3 - p1) / 6.0;
+    let max_speed = 3.0 * p1.distance(b1).max(b1.distance(b2)).max(b2.distance(p2));
+    let steps = (max_speed / spacing.max(1.0)).ceil().max(1.0) as u32;
+    (1..=steps)
+        .map(|i| {
+            let t = i as f32 / steps as f32;
+            let t2 = t * t;
+            let t3 = t2 * t;
+            0.5 * (2.0 * p1
+                + (p2 - p0) * t
+                + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+                + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+        })
+        .collect()
+}
+
+/// Something that can scratch a card, each one drives its own stroke
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScratchPointer {
+    Mouse,
+    Touch(u64),
+}
+
+/// Strokes in progress on a card, one per pointer scratching it
+#[derive(Component, Debug, Clone, Default)]
+pub struct ScratchStrokes(pub HashMap<ScratchPointer, StrokeInterpolator>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPACING: f32 = 4.0;
+    /// Far apart and uneven, like a fast flick that turns sharply
+    const SAMPLES: [Vec2; 6] = [
+        Vec2::new(0.0, 0.0),
+        Vec2::new(120.0, 0.0),
+        Vec2::new(124.0, 6.0),
+        Vec2::new(300.0, 220.0),
+        Vec2::new(10.0, 300.0),
+        Vec2::new(12.0, 302.0),
+    ];
+
+    /// Centres of a whole stroke, and how many of them only `end` produced
+    fn stroke(smooth: bool) -> (Vec<Vec2>, usize) {
+        let mut interpolator = StrokeInterpolator::default();
+        let mut centres = Vec::new();
+        for sample in SAMPLES {
+            centres.extend(interpolator.push(sample, SPACING, smooth));
+        }
+        let tail = interpolator.end(SPACING, smooth);
+        let flushed = tail.len();
+        centres.extend(tail);
+        assert!(!interpolator.is_active());
+        (centres, flushed)
+    }
+
+    fn assert_spaced(centres: &[Vec2]) {
+        for pair in centres.windows(2) {
+            let gap = pair[0].distance(pair[1]);
+            assert!(
+                gap <= SPACING + 1e-3,
+                "{} to {} is {gap} apart",
+                pair[0],
+                pair[1]
+            );
+        }
+    }
+
+    #[test]
+    fn straight_strokes_are_evenly_spaced() {
+        let (centres, flushed) = stroke(false);
+        assert_spaced(&centres);
+        assert_eq!(centres.first(), Some(&SAMPLES[0]));
+        // Nothing is held back without smoothing
+        assert_eq!(flushed, 0);
+        assert_eq!(centres.last(), SAMPLES.last());
+    }
+
+    #[test]
+    fn smooth_strokes_are_evenly_spaced_and_end_on_the_last_sample() {
+        let (centres, flushed) = stroke(true);
+        assert_spaced(&centres);
+        assert_eq!(centres.first(), Some(&SAMPLES[0]));
+        assert!(flushed > 0);
+        assert!(centres.last().unwrap().distance(*SAMPLES.last().unwrap()) < 1e-3);
+    }
+}
@@ -0,0 +1,304 @@
+use std::{fmt, io, path::Path, sync::Arc};
+
+use bevy::prelude::*;
+
+use super::{
+    BrushFootprint, MAX_STAMP_OFFSET, MAX_STAMP_RADIUS, ScratchBackend, ScratchBlend, ScratchMask,
+    ScratchStamp,
+};
+
+const LOG_MAGIC: &[u8; 4] = b"SCRL";
+const LOG_VERSION: u8 = 1;
+const NO_FOOTPRINT: u32 = u32::MAX;
+
+/// A stamp and when it was applied
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoggedStamp {
+    /// Seconds since startup
+    pub time: f64,
+    pub stamp: ScratchStamp,
+}
+
+/// Every stamp scratched into a card, in order.
+///
+/// Insert it with a [`super::ScratchCard`] to record; replaying it onto a blank
+/// mask of the same size rebuilds the exact mask, so a reveal can be verified
+/// or a bug report reproduced. Edits made outside strokes (restores,
+/// [`ScratchMask::reveal_all`]) are not recorded.
+///
+/// ```
+/// use bevy::prelude::*;
+/// use mask_2d::scratch_card::{ScratchStamp, ScratchStrokeLog};
+///
+/// let mut log = ScratchStrokeLog::default();
+/// log.record(0.0, ScratchStamp::circle(IVec2::new(10, 10), 6, 255));
+/// log.record(0.1, ScratchStamp::circle(IVec2::new(20, 12), 6, 255));
+///
+/// let received = ScratchStrokeLog::from_bytes(&log.to_bytes()).unwrap();
+/// let size = UVec2::new(32, 24);
+/// assert_eq!(received.reconstruct(size), log.reconstruct(size));
+/// ```
+#[derive(Component, Debug, Clone, Default, PartialEq)]
+pub struct ScratchStrokeLog {
+    pub stamps: Vec<LoggedStamp>,
+}
+
+/// Why a stroke log couldn't be read
+#[derive(Debug)]
+pub enum ScratchLogError {
+    Io(io::Error),
+    /// The bytes don't start with the log magic
+    NotALog,
+    UnsupportedVersion(u8),
+    /// The data ends early, references a missing footprint or holds a stamp out of range
+    Corrupt,
+}
+
+impl fmt::Display for ScratchLogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "failed to access scratch stroke log: {err}"),
+            Self::NotALog => write!(f, "data is not a scratch stroke log"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported scratch stroke log version {version}")
+            }
+            Self::Corrupt => write!(f, "scratch stroke log is corrupt"),
+        }
+    }
+}
+
+impl std::error::Error for ScratchLogError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ScratchLogError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl ScratchStrokeLog {
+    pub fn record(&mut self, time: f64, stamp: ScratchStamp) {
+        self.stamps.push(LoggedStamp { time, stamp });
+    }
+
+    /// Apply every stamp to a mask in recorded order
+    pub fn replay(&self, mask: &mut ScratchMask) {
+        for logged in &self.stamps {
+            mask.scratch(logged.stamp.clone());
+        }
+    }
+
+    /// Mask bytes produced by replaying onto a blank mask of `size` pixels
+    pub fn reconstruct(&self, size: UVec2) -> Vec<u8> {
+        let mut mask = ScratchMask::new(size, Handle::default(), ScratchBackend::Cpu);
+        for logged in &self.stamps {
+            mask.paint(&logged.stamp);
+        }
+        mask.data
+    }
+
+    /// Encode as a footprint table followed by the stamps, all little endian
+    pub fn to_bytes(&self) -> Vec<u8> {
+        // Brush footprints are shared between stamps, store each one once
+        let mut footprints: Vec<&Arc<BrushFootprint>> = Vec::new();
+        let indices = self
+            .stamps
+            .iter()
+            .map(|logged| match &logged.stamp.footprint {
+                Some(footprint) => {
+                    let index = footprints
+                        .iter()
+                        .position(|known| Arc::ptr_eq(known, footprint))
+                        .unwrap_or_else(|| {
+                            footprints.push(footprint);
+                            footprints.len() - 1
+                        });
+                    index as u32
+                }
+                None => NO_FOOTPRINT,
+            })
+            .collect::<Vec<_>>();
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(LOG_MAGIC);
+        bytes.push(LOG_VERSION);
+        bytes.extend_from_slice(&(footprints.len() as u32).to_le_bytes());
+        for footprint in &footprints {
+            bytes.extend_from_slice(&footprint.size.to_le_bytes());
+            for weight in &footprint.weights {
+                bytes.extend_from_slice(&weight.to_le_bytes());
+            }
+        }
+        bytes.extend_from_slice(&(self.stamps.len() as u32).to_le_bytes());
+        for (logged, footprint) in self.stamps.iter().zip(indices) {
+            let stamp = &logged.stamp;
+            bytes.extend_from_slice(&logged.time.to_le_bytes());
+            bytes.extend_from_slice(&stamp.center.x.to_le_bytes());
+            bytes.extend_from_slice(&stamp.center.y.to_le_bytes());
+            bytes.extend_from_slice(&stamp.radius.to_le_bytes());
+            bytes.push(stamp.value);
+            bytes.extend_from_slice(&stamp.hardness.to_le_bytes());
+            bytes.push(match stamp.blend {
+                ScratchBlend::Max => 0,
+                ScratchBlend::Additive => 1,
+            });
+            bytes.extend_from_slice(&footprint.to_le_bytes());
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ScratchLogError> {
+        let mut reader = Reader(bytes);
+        if reader.take(4).ok() != Some(&LOG_MAGIC[..]) {
+            return Err(ScratchLogError::NotALog);
+        }
+        let version = reader.u8()?;
+        if version != LOG_VERSION {
+            return Err(ScratchLogError::UnsupportedVersion(version));
+        }
+
+        let footprint_count = reader.u32()?;
+        let mut footprints = Vec::new();
+        for _ in 0..footprint_count {
+            let size = reader.u32()?;
+            if size > 2 * MAX_STAMP_RADIUS as u32 + 1 {
+                return Err(ScratchLogError::Corrupt);
+            }
+            let len = (size as usize)
+                .checked_mul(size as usize)
+                .ok_or(ScratchLogError::Corrupt)?;
+            let weights = (0..len)
+                .map(|_| reader.f32())
+                .collect::<Result<Vec<_>, _>>()?;
+            footprints.push(Arc::new(BrushFootprint { size, weights }));
+        }
+
+        let stamp_count = reader.u32()?;
+        let mut stamps = Vec::new();
+        for _ in 0..stamp_count {
+            let time = reader.f64()?;
+            let center = IVec2::new(reader.i32()?, reader.i32()?);
+            let radius = reader.i32()?;
+            let value = reader.u8()?;
+            let hardness = reader.f32()?;
+            // Logs come from players, stamps must stay cheap to paint and free of overflow
+            if !(0..=MAX_STAMP_RADIUS).contains(&radius)
+                || !center.cmpge(IVec2::splat(-MAX_STAMP_OFFSET)).all()
+                || !center.cmple(IVec2::splat(MAX_STAMP_OFFSET)).all()
+                || !hardness.is_finite()
+            {
+                return Err(ScratchLogError::Corrupt);
+            }
+            let blend = match reader.u8()? {
+                0 => ScratchBlend::Max,
+                1 => ScratchBlend::Additive,
+                _ => return Err(ScratchLogError::Corrupt),
+            };
+            let footprint = match reader.u32()? {
+                NO_FOOTPRINT => None,
+                index => Some(
+                    footprints
+                        .get(index as usize)
+                        .ok_or(ScratchLogError::Corrupt)?
+                        .clone(),
+                ),
+            };
+            stamps.push(LoggedStamp {
+                time,
+                stamp: ScratchStamp {
+                    center,
+                    radius,
+                    value,
+                    hardness,
+                    blend,
+                    footprint,
+                },
+            });
+        }
+        if !reader.0.is_empty() {
+            return Err(ScratchLogError::Corrupt);
+        }
+
+        Ok(Self { stamps })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ScratchLogError> {
+        std::fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ScratchLogError> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+}
+
+/// Little endian cursor over the encoded log
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ScratchLogError> {
+        if self.0.len() < len {
+            return Err(ScratchLogError::Corrupt);
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ScratchLogError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, ScratchLogError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, ScratchLogError> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn i32(&mut self) -> Result<i32, ScratchLogError> {
+        self.array().map(i32::from_le_bytes)
+    }
+
+    fn f32(&mut self) -> Result<f32, ScratchLogError> {
+        self.array().map(f32::from_le_bytes)
+    }
+
+    fn f64(&mut self) -> Result<f64, ScratchLogError> {
+        self.array().map(f64::from_le_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_bytes(center: IVec2, radius: i32) -> Vec<u8> {
+        let mut log = ScratchStrokeLog::default();
+        log.record(0.0, ScratchStamp::circle(center, radius, 255));
+        log.to_bytes()
+    }
+
+    #[test]
+    fn rejects_out_of_range_stamps() {
+        assert!(ScratchStrokeLog::from_bytes(&log_bytes(IVec2::splat(8), 4)).is_ok());
+        for (center, radius) in [
+            (IVec2::splat(8), 1_000_000),
+            (IVec2::splat(8), -1),
+            (IVec2::new(i32::MAX, 0), 4),
+            (IVec2::new(0, i32::MIN), 4),
+        ] {
+            assert!(matches!(
+                ScratchStrokeLog::from_bytes(&log_bytes(center, radius)),
+                Err(ScratchLogError::Corrupt)
+            ));
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use bevy::{
+    math::URect,
+    platform::collections::HashSet,
+    prelude::*,
+    render::{
+        Extract, ExtractSchedule, Render, RenderApp, RenderSet,
+        render_asset::RenderAssets,
+        render_resource::{
+            Extent3d, Origin3d, TexelCopyBufferLayout, TexelCopyTextureInfo, TextureAspect,
+        },
+        renderer::RenderQueue,
+        texture::GpuImage,
+    },
+};
+
+use super::{ScratchMask, ScratchStamp};
+
+/// Writes the dirty rectangles of every [`ScratchMask`] into its existing GPU texture
+pub(super) struct ScratchMaskUploadPlugin;
+
+impl Plugin for ScratchMaskUploadPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ScratchMaskUploads>()
+            .add_systems(PostUpdate, queue_mask_uploads);
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .init_resource::<PendingMaskWork>()
+            .add_systems(ExtractSchedule, extract_mask_uploads)
+            .add_systems(
+                Render,
+                write_mask_uploads.in_set(RenderSet::PrepareResources),
+            );
+    }
+}
+
+/// A dirty rectangle of a mask and its tightly packed bytes
+#[derive(Clone)]
+struct MaskUpload {
+    image: AssetId<Image>,
+    rect: URect,
+    bytes: Vec<u8>,
+}
+
+/// Uploads produced by the main world this frame
+#[derive(Resource, Default)]
+pub(super) struct ScratchMaskUploads(Vec<MaskUpload>);
+
+/// Work on a mask texture in the render world
+#[derive(Clone)]
+pub(super) enum MaskWork {
+    Upload {
+        rect: URect,
+        bytes: Vec<u8>,
+    },
+    /// Stamps for `scratch_update.wgsl`, in the order they were scratched
+    Stamps(Vec<ScratchStamp>),
+}
+
+/// Uploads and GPU stamps waiting in the render world, in the order the main world made them.
+///
+/// Texture writes land before the frame's compute passes, so an upload queued behind
+/// stamps waits a frame for them to be dispatched; otherwise the stamps would paint over it.
+/// Work for despawned masks is dropped, as are all stamps once the compute painter fails.
+#[derive(Resource, Default)]
+pub(super) struct PendingMaskWork(pub(super) Vec<(AssetId<Image>, MaskWork)>);
+
+pub(super) fn queue_mask_uploads(
+    mut uploads: ResMut<ScratchMaskUploads>,
+    mut masks: Query<&mut ScratchMask>,
+) {
+    uploads.0.clear();
+    for mut mask in &mut masks {
+        // Handing the rectangle over doesn't change the mask, `Changed<ScratchMask>` stays quiet
+        let Some((rect, bytes)) = mask.bypass_change_detection().take_dirty() else {
+            continue;
+        };
+        uploads.0.push(MaskUpload {
+            image: mask.image.id(),
+            rect,
+            bytes,
+        });
+    }
+}
+
+/// A frame's uploads go first, stamps left on a mask after its upload was taken are newer
+pub(super) fn extract_mask_uploads(
+    uploads: Extract<Res<ScratchMaskUploads>>,
+    masks: Extract<Query<&ScratchMask>>,
+    mut pending: ResMut<PendingMaskWork>,
+) {
+    // Work for masks that are gone would wait forever for a texture
+    let live = masks
+        .iter()
+        .map(|mask| mask.image.id())
+        .collect::<HashSet<_>>();
+    pending.0.retain(|(image, _)| live.contains(image));
+    pending.0.extend(uploads.0.iter().map(|upload| {
+        (
+            upload.image,
+            MaskWork::Upload {
+                rect: upload.rect,
+                bytes: upload.bytes.clone(),
+            },
+        )
+    }));
+}
+
+fn write_mask_uploads(
+    mut pending: ResMut<PendingMaskWork>,
+    gpu_images: Res<RenderAssets<GpuImage>>,
+    queue: Res<RenderQueue>,
+) {
+    // Images with older work still waiting, later uploads keep their place behind it
+    let mut blocked = HashSet::new();
+    pending.0.retain(|(image, work)| {
+        if blocked.contains(image) {
+            return true;
+        }
+        // The texture is prepared a frame after the image is added, keep the upload until then
+        let (MaskWork::Upload { rect, bytes }, Some(gpu_image)) = (work, gpu_images.get(*image))
+        else {
+            blocked.insert(*image);
+            return true;
+        };
+
+        let width = rect.width();
+        let height = rect.height();
+        queue.write_texture(
+            TexelCopyTextureInfo {
+                texture: &gpu_image.texture,
+                mip_level: 0,
+                origin: Origin3d {
+                    x: rect.min.x,
+                    y: rect.min.y,
+                    z: 0,
+                },
+                aspect: TextureAspect::All,
+            },
+            bytes,
+            TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(width),
+                rows_per_image: Some(height),
+            },
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        false
+    });
+}
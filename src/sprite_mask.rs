@@ -0,0 +1,479 @@
+//! Clip a whole sub-tree of 2D entities by a mask: put a [`SpriteMask`] on a parent and its
+//! descendant [`Sprite`]s and [`Mesh2d`]s only show through the mask's shape.
+//!
+//! Each mask renders its descendants with its own camera into an image, on a render layer of
+//! its own, and draws that image on a [`Mask2DMaterial`] quad in their place.
+
+use bevy::{
+    asset::RenderAssetUsages,
+    platform::collections::HashSet,
+    prelude::*,
+    render::{
+        camera::{RenderTarget, ScalingMode},
+        render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages},
+        view::RenderLayers,
+    },
+};
+
+use crate::mask2d::{Mask2DMaterial, Mask2DPlugin, MaskChannel, MaskThreshold, MaskTransform};
+
+/// Layers below this are left to the app, masks take the lowest free layer from here on
+pub const FIRST_SPRITE_MASK_LAYER: usize = 16;
+/// Width and height of the generated ellipse mask image
+const ELLIPSE_MASK_SIZE: u32 = 256;
+
+pub struct SpriteMaskPlugin;
+
+impl Plugin for SpriteMaskPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<Mask2DPlugin>() {
+            app.add_plugins(Mask2DPlugin);
+        }
+        app.add_systems(
+            PostUpdate,
+            (
+                remove_sprite_masks,
+                setup_sprite_masks,
+                propagate_sprite_masks,
+            )
+                .chain()
+                .before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// Outline of a [`SpriteMask`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpriteMaskShape {
+    /// The whole rectangle, like a scroll view's viewport
+    Rect,
+    /// The ellipse filling the rectangle, for portholes
+    Ellipse,
+    /// A mask image stretched over the rectangle, read through [`SpriteMask::channel`]
+    Image(Handle<Image>),
+}
+
+/// Clips every descendant [`Sprite`] and [`Mesh2d`] to a shape centred on this entity.
+///
+/// The settings are read once when the mask is set up; afterwards the mask can be tweaked
+/// through its [`Mask2DMaterial`]. Descendants' [`RenderLayers`] are managed by the mask,
+/// and a mask inside another mask is clipped by it in turn.
+#[derive(Component, Debug, Clone)]
+#[require(Transform, Visibility)]
+pub struct SpriteMask {
+    pub shape: SpriteMaskShape,
+    /// Width and height of the clipped area, in the entity's local units
+    pub size: Vec2,
+    /// Rendered pixels per local unit, raise it for masks scaled up by their transform
+    pub resolution: f32,
+    pub channel: MaskChannel,
+    /// Clip away the shape instead of everything around it
+    pub invert: bool,
+    pub threshold: Option<MaskThreshold>,
+}
+
+impl SpriteMask {
+    pub fn new(shape: SpriteMaskShape, size: Vec2) -> Self {
+        Self {
+            shape,
+            size,
+            resolution: 1.0,
+            channel: MaskChannel::Alpha,
+            invert: false,
+            threshold: None,
+        }
+    }
+
+    /// Size of the image the descendants are rendered into
+    pub fn target_size(&self) -> UVec2 {
+        (self.size * self.resolution)
+            .ceil()
+            .max(Vec2::ONE)
+            .as_uvec2()
+    }
+}
+
+/// Camera and render layer a set-up [`SpriteMask`] renders its descendants with
+#[derive(Component, Debug, Clone)]
+pub struct SpriteMaskView {
+    pub camera: Entity,
+    pub layer: usize,
+    /// What the camera renders, straight from the descendants with premultiplied alpha
+    pub target: Handle<Image>,
+}
+
+/// Marks a descendant whose [`RenderLayers`] were set by the mask it sits under
+#[derive(Component, Debug, Clone)]
+pub struct SpriteMasked {
+    pub mask: Entity,
+    /// Layers the entity had before it was masked, put back once it leaves the mask
+    pub previous_layers: Option<RenderLayers>,
+}
+
+/// Attach the camera, render target and masked quad to newly added masks
+fn setup_sprite_masks(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<Mask2DMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+    // Generated rectangle and ellipse images, shared by all masks
+    mut shape_images: Local<Option<(Handle<Image>, Handle<Image>)>>,
+    views: Query<&SpriteMaskView>,
+    masks: Query<(Entity, &SpriteMask), Without<SpriteMaskView>>,
+) {
+    let mut used_layers = views.iter().map(|view| view.layer).collect::<HashSet<_>>();
+
+    for (entity, mask) in &masks {
+        let (rect_image, ellipse_image) = shape_images
+            .get_or_insert_with(|| {
+                (
+                    images.add(create_shape_image(UVec2::ONE, |_| 1.0)),
+                    images.add(create_shape_image(
+                        UVec2::splat(ELLIPSE_MASK_SIZE),
+                        ellipse_coverage,
+                    )),
+                )
+            })
+            .clone();
+        let mask_image = match &mask.shape {
+            SpriteMaskShape::Rect => rect_image,
+            SpriteMaskShape::Ellipse => ellipse_image,
+            SpriteMaskShape::Image(image) => image.clone(),
+        };
+
+        let layer = (FIRST_SPRITE_MASK_LAYER..)
+            .find(|layer| !used_layers.contains(layer))
+            .unwrap();
+        used_layers.insert(layer);
+
+        let target = images.add(create_target_image(mask.target_size()));
+        // A child with an identity transform sees exactly the mask's rectangle
+        let camera = commands
+            .spawn((
+                Camera2d,
+                Camera {
+                    target: RenderTarget::Image(target.clone().into()),
+                    order: -1,
+                    clear_color: ClearColorConfig::Custom(Color::NONE),
+                    ..default()
+                },
+                Projection::Orthographic(OrthographicProjection {
+                    scaling_mode: ScalingMode::Fixed {
+                        width: mask.size.x,
+                        height: mask.size.y,
+                    },
+                    ..OrthographicProjection::default_2d()
+                }),
+                RenderLayers::layer(layer),
+                ChildOf(entity),
+            ))
+            .id();
+
+        let material = materials.add(Mask2DMaterial {
+            channel: mask.channel,
+            invert: mask.invert,
+            threshold: mask.threshold,
+            // Rotating the mask through the material then keeps its shape on wide masks
+            mask_transform: MaskTransform {
+                aspect: mask.size.x / mask.size.y,
+                ..default()
+            },
+            premultiplied_main: true,
+            ..Mask2DMaterial::new(target.clone(), mask_image)
+        });
+
+        commands.entity(entity).insert((
+            Mesh2d(meshes.add(Rectangle::from_size(mask.size))),
+            MeshMaterial2d(material),
+            SpriteMaskView {
+                camera,
+                layer,
+                target,
+            },
+        ));
+    }
+}
+
+/// Tear down the camera and quad of entities that lost their [`SpriteMask`]
+fn remove_sprite_masks(
+    mut commands: Commands,
+    views: Query<(Entity, &SpriteMaskView), Without<SpriteMask>>,
+) {
+    for (entity, view) in &views {
+        commands.entity(view.camera).despawn();
+        commands
+            .entity(entity)
+            .remove::<(SpriteMaskView, Mesh2d, MeshMaterial2d<Mask2DMaterial>)>();
+    }
+}
+
+/// Render layers and marker of an entity a mask may have to move
+type MaskableQuery = (Option<&'static RenderLayers>, Option<&'static SpriteMasked>);
+/// Marker and entity of everything a mask moved
+type MaskedQuery = (Entity, &'static SpriteMasked);
+type Drawable = Or<(With<Sprite>, With<Mesh2d>)>;
+
+/// Move descendants onto their nearest mask's layer, and back once they leave it.
+///
+/// Nested masks render before the masks around them.
+fn propagate_sprite_masks(
+    mut commands: Commands,
+    views: Query<(Entity, &SpriteMaskView), With<SpriteMask>>,
+    parents: Query<&ChildOf>,
+    children: Query<&Children>,
+    drawables: Query<MaskableQuery, Drawable>,
+    masked: Query<MaskedQuery>,
+    mut cameras: Query<&mut Camera>,
+) {
+    let mut visited = HashSet::new();
+    // Entity, the mask it belongs to and the number of masks around that mask
+    let mut stack = Vec::new();
+    for (root, view) in &views {
+        if parents
+            .iter_ancestors(root)
+            .any(|ancestor| views.contains(ancestor))
+        {
+            continue;
+        }
+        set_camera_order(&mut cameras, view, 0);
+        if let Ok(root_children) = children.get(root) {
+            stack.extend(root_children.iter().map(|child| (child, root, 0)));
+        }
+    }
+
+    while let Some((entity, mask, depth)) = stack.pop() {
+        let (_, view) = views.get(mask).unwrap();
+        if view.camera == entity {
+            continue;
+        }
+
+        if let Ok((layers, marker)) = drawables.get(entity) {
+            visited.insert(entity);
+            let layer = RenderLayers::layer(view.layer);
+            if layers != Some(&layer) || marker.is_none_or(|marker| marker.mask != mask) {
+                // Moving between masks keeps the layers from before the first one
+                let previous_layers = match marker {
+                    Some(marker) => marker.previous_layers.clone(),
+                    None => layers.cloned(),
+                };
+                commands.entity(entity).insert((
+                    layer,
+                    SpriteMasked {
+                        mask,
+                        previous_layers,
+                    },
+                ));
+            }
+        }
+
+        // A nested mask clips its own sub-tree
+        let (owner, depth) = match views.get(entity) {
+            Ok((_, view)) => {
+                set_camera_order(&mut cameras, view, depth + 1);
+                (entity, depth + 1)
+            }
+            Err(_) => (mask, depth),
+        };
+        if let Ok(grandchildren) = children.get(entity) {
+            stack.extend(grandchildren.iter().map(|child| (child, owner, depth)));
+        }
+    }
+
+    for (entity, marker) in &masked {
+        if visited.contains(&entity) {
+            continue;
+        }
+        let mut entity = commands.entity(entity);
+        entity.remove::<SpriteMasked>();
+        match &marker.previous_layers {
+            Some(layers) => entity.insert(layers.clone()),
+            None => entity.remove::<RenderLayers>(),
+        };
+    }
+}
+
+/// Masks nested `depth` deep render that many steps before the outermost ones
+fn set_camera_order(cameras: &mut Query<&mut Camera>, view: &SpriteMaskView, depth: usize) {
+    let order = -1 - depth as isize;
+    if let Ok(mut camera) = cameras.get_mut(view.camera)
+        && camera.order != order
+    {
+        camera.order = order;
+    }
+}
+
+/// Transparent image a mask camera can render into and a material can sample
+fn create_target_image(size: UVec2) -> Image {
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0; 4],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    image.texture_descriptor.usage =
+        TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST | TextureUsages::RENDER_ATTACHMENT;
+    image
+}
+
+/// White mask image whose alpha is `coverage` at each pixel centre's UV
+fn create_shape_image(size: UVec2, coverage: impl Fn(Vec2) -> f32) -> Image {
+    let data = (0..size.y)
+        .flat_map(|y| (0..size.x).map(move |x| UVec2::new(x, y)))
+        .flat_map(|pixel| {
+            let uv = (pixel.as_vec2() + 0.5) / size.as_vec2();
+            let alpha = (coverage(uv).clamp(0.0, 1.0) * 255.0).round() as u8;
+            [255, 255, 255, alpha]
+        })
+        .collect();
+    Image::new(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8Unorm,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
+
+/// Inscribed ellipse with a one pixel wide anti-aliased edge
+fn ellipse_coverage(uv: Vec2) -> f32 {
+    let distance = ((uv - 0.5) * 2.0).length();
+    (1.0 - distance) * ELLIPSE_MASK_SIZE as f32 * 0.5 + 0.5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.init_resource::<Assets<Mesh>>()
+            .init_resource::<Assets<Mask2DMaterial>>()
+            .init_resource::<Assets<Image>>()
+            .add_systems(
+                Update,
+                (
+                    remove_sprite_masks,
+                    setup_sprite_masks,
+                    propagate_sprite_masks,
+                )
+                    .chain(),
+            );
+        app
+    }
+
+    fn mask() -> SpriteMask {
+        SpriteMask::new(SpriteMaskShape::Rect, Vec2::splat(32.0))
+    }
+
+    fn view(app: &App, mask: Entity) -> SpriteMaskView {
+        app.world().get::<SpriteMaskView>(mask).unwrap().clone()
+    }
+
+    fn layers(app: &App, entity: Entity) -> Option<RenderLayers> {
+        app.world().get::<RenderLayers>(entity).cloned()
+    }
+
+    fn camera_order(app: &App, view: &SpriteMaskView) -> isize {
+        app.world().get::<Camera>(view.camera).unwrap().order
+    }
+
+    #[test]
+    fn nested_masks_get_their_own_layers_and_render_first() {
+        let mut app = app();
+        let outer = app.world_mut().spawn(mask()).id();
+        let sprite = app
+            .world_mut()
+            .spawn((Sprite::default(), ChildOf(outer)))
+            .id();
+        let inner = app.world_mut().spawn((mask(), ChildOf(outer))).id();
+        let nested = app
+            .world_mut()
+            .spawn((Sprite::default(), ChildOf(inner)))
+            .id();
+        app.update();
+
+        let (outer_view, inner_view) = (view(&app, outer), view(&app, inner));
+        assert_ne!(outer_view.layer, inner_view.layer);
+        assert!(outer_view.layer >= FIRST_SPRITE_MASK_LAYER);
+        assert!(inner_view.layer >= FIRST_SPRITE_MASK_LAYER);
+
+        let outer_layer = Some(RenderLayers::layer(outer_view.layer));
+        assert_eq!(layers(&app, sprite), outer_layer);
+        // The inner mask's quad is drawn by the outer mask
+        assert_eq!(layers(&app, inner), outer_layer);
+        assert_eq!(
+            layers(&app, nested),
+            Some(RenderLayers::layer(inner_view.layer))
+        );
+        assert_eq!(app.world().get::<SpriteMasked>(nested).unwrap().mask, inner);
+
+        assert_eq!(camera_order(&app, &outer_view), -1);
+        assert_eq!(camera_order(&app, &inner_view), -2);
+    }
+
+    #[test]
+    fn leaving_a_mask_restores_the_previous_layers() {
+        let mut app = app();
+        let mask = app.world_mut().spawn(mask()).id();
+        let layered = app
+            .world_mut()
+            .spawn((Sprite::default(), RenderLayers::layer(2), ChildOf(mask)))
+            .id();
+        let plain = app
+            .world_mut()
+            .spawn((Sprite::default(), ChildOf(mask)))
+            .id();
+        app.update();
+        let layer = Some(RenderLayers::layer(view(&app, mask).layer));
+        assert_eq!(layers(&app, layered), layer);
+        assert_eq!(layers(&app, plain), layer);
+
+        app.world_mut().entity_mut(layered).remove::<ChildOf>();
+        app.world_mut().entity_mut(plain).remove::<ChildOf>();
+        app.update();
+        assert_eq!(layers(&app, layered), Some(RenderLayers::layer(2)));
+        assert_eq!(layers(&app, plain), None);
+        assert!(app.world().get::<SpriteMasked>(layered).is_none());
+        assert!(app.world().get::<SpriteMasked>(plain).is_none());
+    }
+
+    #[test]
+    fn removing_a_mask_tears_it_down_and_frees_its_layer() {
+        let mut app = app();
+        let mask = app.world_mut().spawn(mask()).id();
+        let sprite = app
+            .world_mut()
+            .spawn((Sprite::default(), RenderLayers::layer(1), ChildOf(mask)))
+            .id();
+        app.update();
+        let view = view(&app, mask);
+
+        app.world_mut().entity_mut(mask).remove::<SpriteMask>();
+        app.update();
+        assert!(app.world().get_entity(view.camera).is_err());
+        let torn_down = app.world().entity(mask);
+        assert!(!torn_down.contains::<SpriteMaskView>());
+        assert!(!torn_down.contains::<Mesh2d>());
+        assert!(!torn_down.contains::<MeshMaterial2d<Mask2DMaterial>>());
+        assert_eq!(layers(&app, sprite), Some(RenderLayers::layer(1)));
+
+        let replacement = app.world_mut().spawn(self::mask()).id();
+        app.update();
+        assert_eq!(
+            app.world()
+                .get::<SpriteMaskView>(replacement)
+                .unwrap()
+                .layer,
+            view.layer
+        );
+    }
+}